@@ -0,0 +1,65 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+
+/// Newline-delimited JSON events, written while the tests are running.
+pub struct EventLog {
+    writer: Mutex<Box<dyn Write + Send>>,
+    failed: AtomicBool,
+}
+
+impl EventLog {
+    /// `target` is either a file path, or `fd:N` to write to an already opened file descriptor.
+    pub fn open(target: &str) -> Result<Self, std::io::Error> {
+        let file = match target.strip_prefix("fd:") {
+            Some(fd) => {
+                let fd = fd.parse::<i32>().map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid file descriptor"))?;
+                Self::file_from_fd(fd)?
+            },
+            None => File::create(target)?,
+        };
+        Ok(EventLog { writer: Mutex::new(Box::new(file)), failed: AtomicBool::new(false) })
+    }
+
+    #[cfg(unix)]
+    fn file_from_fd(fd: i32) -> Result<File, std::io::Error> {
+        use std::os::unix::io::FromRawFd;
+        // make sure the descriptor is open, before taking ownership of it
+        std::fs::metadata(format!("/dev/fd/{}", fd))?;
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    #[cfg(not(unix))]
+    fn file_from_fd(_fd: i32) -> Result<File, std::io::Error> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "file descriptors are not supported on this platform"))
+    }
+
+    /// Writes a single event. A broken event log must not abort the tests, so errors are only reported once.
+    pub fn emit(&self, event: &str, mut payload: serde_json::Value) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        if let Some(payload) = payload.as_object_mut() {
+            payload.insert("event".to_owned(), json!(event));
+            payload.insert("time".to_owned(), json!(time as u64));
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        let res = writeln!(writer, "{}", payload).and_then(|_| writer.flush());
+        if let Err(err) = res {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                eprintln!("Warning: failed writing event: {}", err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EventLog")
+    }
+}
@@ -15,20 +15,34 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod events;
 mod project;
 mod test;
 mod testresult;
 mod testrunner;
+mod util;
+mod watch;
 
 use std::fs::write;
+use std::io::IsTerminal;
 
 use clap::{App, Arg, crate_authors, crate_description, crate_version, ArgMatches};
+use regex::Regex;
 
-use crate::testrunner::{Testrunner, TestrunnerOptions, TestrunnerError};
+use crate::events::EventLog;
+use crate::test::launcher::{LAUNCH_ARG, launch};
+use crate::testrunner::{EXIT_CONFIG_ERROR, Isolation, OutputFormat, Selection, Testrunner, TestrunnerOptions, TestrunnerError};
+use crate::watch::Watcher;
 
 
 fn main() {
-    let cli_args = App::new("testrunner")
+    // the testrunner is called by itself to launch the tested program, see `launch`
+    let args = std::env::args().collect::<Vec<String>>();
+    if args.get(1).map(String::as_str) == Some(LAUNCH_ARG) {
+        launch(&args[2..]);
+    }
+
+    let app = App::new("testrunner")
         .version(crate_version!())
         .author(crate_authors!(",\n"))
         .about(crate_description!())
@@ -59,6 +73,41 @@ fn main() {
                 }
             })
             .help("Sets number of tests to run in parallel"))
+        .arg(Arg::with_name("filter")
+            .long("filter")
+            .takes_value(true)
+            .value_name("REGEX")
+            .validator(|regex| Regex::new(&regex).map(|_| ()).map_err(|err| err.to_string()))
+            .help("Runs only testcases with a name matching <REGEX>"))
+        .arg(Arg::with_name("only")
+            .long("only")
+            .takes_value(true)
+            .value_name("NUMBERS")
+            .validator(|list| Selection::parse_numbers(&list).map(|_| ()))
+            .help("Runs only testcases with the given numbers, like 3,5-8"))
+        .arg(Arg::with_name("tag")
+            .long("tag")
+            .takes_value(true)
+            .value_name("TAG")
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(true)
+            .help("Runs only testcases with the given tag, may be given multiple times"))
+        .arg(Arg::with_name("fail-fast")
+            .long("fail-fast")
+            .conflicts_with("max-failures")
+            .help("Skips remaining testcases after the first failed testcase"))
+        .arg(Arg::with_name("max-failures")
+            .long("max-failures")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|num| {
+                match num.parse::<usize>() {
+                    Ok(num) if num > 0 => Ok(()),
+                    _ => Err(format!("not a positive number: {}", num)),
+                }
+            })
+            .help("Skips remaining testcases after <N> failed testcases"))
         .arg(Arg::with_name("prot-mode")
             .short("p")
             .long("protected-mode")
@@ -77,41 +126,174 @@ fn main() {
             .value_name("JSON_OUTPUT")
             .default_value("testreport.json")
             .help("Generates JSON report"))
+        .arg(Arg::with_name("markdown")
+            .long("markdown-output")
+            .takes_value(true)
+            .value_name("MARKDOWN_OUTPUT")
+            .default_value("testreport.md")
+            .help("Generates Markdown report"))
+        .arg(Arg::with_name("junit")
+            .long("junit-output")
+            .takes_value(true)
+            .value_name("JUNIT_OUTPUT")
+            .default_value("testreport.xml")
+            .help("Generates JUnit XML report"))
+        .arg(Arg::with_name("events")
+            .long("events")
+            .takes_value(true)
+            .value_name("EVENTS_OUTPUT")
+            .help("Writes NDJSON events while running the tests, to a file or to fd:<N>"))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(&["human", "tap"])
+            .default_value("human")
+            .help("Sets format of the console output"))
+        .arg(Arg::with_name("watch")
+            .short("w")
+            .long("watch")
+            .help("Reruns the testcases whenever files of the project change"))
+        .arg(Arg::with_name("show-diff")
+            .long("show-diff")
+            .help("Prints a diff of the outputs of failed testcases"))
+        .arg(Arg::with_name("color")
+            .long("color")
+            .takes_value(true)
+            .value_name("WHEN")
+            .possible_values(&["auto", "always", "never"])
+            .default_value("auto")
+            .help("Sets when to use colors in the console output"))
+        .arg(Arg::with_name("isolation")
+            .long("isolation")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(&["none", "namespaces"])
+            .default_value("none")
+            .help("Sets isolation of the tested program from network and other processes"))
+        .arg(Arg::with_name("keep-sandboxes")
+            .long("keep-sandboxes")
+            .help("Keeps the sandbox directories of testcases after running them"))
         .arg(Arg::with_name("sudo")
             .long("sudo")
             .takes_value(true)
             .value_name("USER")
             .hidden(true)
-            .help("Runs program through sudo as user <USER>"))
-        .get_matches();
+            .help("Runs program through sudo as user <USER>"));
 
+    // clap exits with 1 on invalid arguments, which is reserved for failed testcases
+    let cli_args = match app.get_matches_safe() {
+        Ok(cli_args) => cli_args,
+        Err(err) if err.use_stderr() => {
+            eprintln!("{}", err.message);
+            std::process::exit(EXIT_CONFIG_ERROR);
+        },
+        Err(err) => err.exit(),
+    };
 
     match run(cli_args) {
-        Ok(()) => (),
+        Ok(exit_code) => std::process::exit(exit_code),
         Err(err) => {
             eprintln!("Error: {}", err.to_string());
-            std::process::exit(2);
+            std::process::exit(err.exit_code());
         },
     }
 }
 
-fn run(cli_args: ArgMatches) -> Result<(), TestrunnerError> {
+fn run(cli_args: ArgMatches) -> Result<i32, TestrunnerError> {
+    let events = match cli_args.value_of("events") {
+        Some(target) => Some(EventLog::open(target).map_err(|err| TestrunnerError::EventLogError(target.to_owned(), err.to_string()))?),
+        None => None,
+    };
+
     let options = TestrunnerOptions {
         protected_mode: cli_args.occurrences_of("prot-mode") > 0,
         ws_hints: cli_args.occurrences_of("no-wshints") == 0,
         sudo: cli_args.value_of("sudo").map(|e| e.to_string()),
         jobs: cli_args.value_of("jobs").unwrap().parse().unwrap(),
+        keep_sandboxes: cli_args.occurrences_of("keep-sandboxes") > 0,
+        isolation: match cli_args.value_of("isolation").unwrap() {
+            "namespaces" => Isolation::Namespaces,
+            _ => Isolation::None,
+        },
+        format: match cli_args.value_of("format").unwrap() {
+            "tap" => OutputFormat::Tap,
+            _ => OutputFormat::Human,
+        },
+        events,
+        show_diff: cli_args.occurrences_of("show-diff") > 0,
+        max_failures: if cli_args.occurrences_of("fail-fast") > 0 {
+            Some(1)
+        }
+        else {
+            cli_args.value_of("max-failures").map(|num| num.parse().unwrap())
+        },
+        selection: Selection {
+            filter: cli_args.value_of("filter").map(|regex| Regex::new(regex).unwrap()),
+            only: cli_args.value_of("only").map(|list| Selection::parse_numbers(list).unwrap()),
+            tags: cli_args.values_of("tag").map(|tags| tags.map(|tag| tag.to_owned()).collect()),
+        },
+        color: match cli_args.value_of("color").unwrap() {
+            "always" => true,
+            "never" => false,
+            // see https://no-color.org
+            _ => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty()),
+        },
     };
 
     let mut runner = Testrunner::from_file(cli_args.value_of("config").unwrap(), options)?;
-    runner.run_tests()?;
+    let mut watcher = if cli_args.occurrences_of("watch") > 0 {
+        Some(Watcher::new(&runner.project_dir()))
+    }
+    else {
+        None
+    };
 
+    loop {
+        let result = runner.run_tests().and_then(|_| write_reports(&runner, &cli_args));
+
+        let watcher = match watcher.as_mut() {
+            Some(watcher) => watcher,
+            None => {
+                result?;
+                return Ok(runner.exit_code());
+            },
+        };
+        // errors while testing do not end watching, like errors while compiling
+        if let Err(err) = result {
+            eprintln!("Error: {}", err.to_string());
+        }
+        runner.options().log(&format!("\nWatching {} for changes ...", watcher.dir().display()));
+        // ignore files generated while compiling and testing
+        watcher.update();
+        watcher.wait_for_change();
+        while let Err(err) = runner.recompile() {
+            eprintln!("Error: {}", err.to_string());
+            watcher.update();
+            watcher.wait_for_change();
+        }
+    }
+}
+
+fn write_reports(runner: &Testrunner, cli_args: &ArgMatches) -> Result<(), TestrunnerError> {
     if cli_args.occurrences_of("json") > 0 {
         let json_out = cli_args.value_of("json").unwrap();
         let output = runner.generate_json_report()?;
         write(json_out, output)?;
     }
 
+    if cli_args.occurrences_of("markdown") > 0 {
+        let markdown_out = cli_args.value_of("markdown").unwrap();
+        let output = runner.generate_markdown_report()?;
+        write(markdown_out, output)?;
+    }
+
+    if cli_args.occurrences_of("junit") > 0 {
+        let junit_out = cli_args.value_of("junit").unwrap();
+        let output = runner.generate_junit_report()?;
+        write(junit_out, output)?;
+    }
+
     let html_out = cli_args.value_of("html").unwrap();
     if cli_args.occurrences_of("prot-mode") > 0 {
         let output = runner.generate_html_report(true)?;
@@ -124,4 +306,3 @@ fn run(cli_args: ArgMatches) -> Result<(), TestrunnerError> {
 
     Ok(())
 }
-
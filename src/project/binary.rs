@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
-use regex::Regex;
 use serde_derive::Serialize;
+use subprocess::{Popen, PopenConfig, PopenError, Redirection};
+use subprocess::unix::PopenExt;
 use thiserror::Error;
 
+use super::build::Build;
 use super::definition::ProjectDefinition;
+use super::diagnostics::{Diagnostic, count_warnings, parse_diagnostics};
+use super::warning_policy::evaluate_warnings;
+use crate::testrunner::TestrunnerOptions;
 
 
 #[derive(Debug, Error)]
@@ -15,15 +20,33 @@ pub enum CompileError {
     BinaryNotFound(String),
     #[error("no Makefile found at: {0}")]
     MakefileNotFound(String),
-    #[error("calling `make` failed: {}", .0.to_string())]
-    MakeFailed(std::io::Error),
+    #[error("no CMakeLists.txt found at: {0}")]
+    CMakeListsNotFound(String),
+    #[error("no build commands configured")]
+    NoBuildCommands,
+    #[error("calling `{0}` failed: {}", .1.to_string())]
+    BuildCommandFailed(String, PopenError),
+    /// the output captured until the build was killed is kept in the `CompilationInfo`
+    #[error("building timed out after {0} seconds")]
+    Timeout(u64, Box<CompilationInfo>),
 }
 
+/// Default for `build_timeout`, in seconds.
+const DEFAULT_BUILD_TIMEOUT: u64 = 300;
+
 #[derive(Debug, Default, Serialize)]
 pub struct CompilationInfo {
     pub warnings: Option<HashMap<String, i32>>,
     pub errors: Option<String>,
     pub compiled: bool,
+    /// the build was killed after `build_timeout`, see `CompileError::Timeout`
+    pub timed_out: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    /// points deducted from the total score for compiler warnings, see `warning_policy`
+    pub warning_penalty: f32,
+    /// full output of the build, if the binary was built by the testrunner
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -33,23 +56,25 @@ pub struct Binary {
 
 impl Binary {
 
-    pub fn from_project_definition(project_definition: &ProjectDefinition) -> Result<Self, CompileError> {
-        // use pre-compiled binary
-        if project_definition.makefile_path.is_none() {
-            if Self::exists(project_definition) {
-                Ok(Binary { info: CompilationInfo { warnings: None, errors: None, compiled: true } })
-            }
-            else {
-                return Err(CompileError::BinaryNotFound(project_definition.binary_path.clone()));
-            }
-        }
-        // use `make`
-        else if project_definition.makefile_path.is_some() {
-            Ok(Binary { info: Self::compile_with_make(project_definition)? })
-        }
-        // satisfy the compiler
-        else {
-            Ok(Binary { info: CompilationInfo { warnings: None, errors: None, compiled: false } })
+    pub fn from_project_definition(project_definition: &ProjectDefinition, options: &TestrunnerOptions) -> Result<Self, CompileError> {
+        match Build::from_project_definition(project_definition) {
+            // use pre-compiled binary
+            None => {
+                if Self::exists(project_definition) {
+                    Ok(Binary { info: CompilationInfo { warnings: None, errors: None, compiled: true, timed_out: false, diagnostics: vec![], warning_penalty: 0.0, stdout: None, stderr: None } })
+                }
+                else {
+                    Err(CompileError::BinaryNotFound(project_definition.binary_path.clone()))
+                }
+            },
+            // use `make`, CMake or the configured commands
+            Some(build) => {
+                let info = Self::compile(project_definition, &build, options)?;
+                if info.compiled && !Self::exists(project_definition) {
+                    return Err(CompileError::BinaryNotFound(project_definition.binary_path.clone()));
+                }
+                Ok(Binary { info })
+            },
         }
     }
 
@@ -57,50 +82,105 @@ impl Binary {
         Path::new(&project_definition.binary_path).is_file()
     }
 
-    fn compile_with_make(project_definition: &ProjectDefinition) -> Result<CompilationInfo, CompileError> {
-        let makefile_path = project_definition.makefile_path.as_ref().unwrap();
-        if !Path::new(&format!("{}/Makefile", &makefile_path)).is_file() {
-            return Err(CompileError::MakefileNotFound(makefile_path.clone()))
+    fn compile(project_definition: &ProjectDefinition, build: &Build, options: &TestrunnerOptions) -> Result<CompilationInfo, CompileError> {
+        let commands = build.commands(project_definition)?;
+        let directory = build.directory(project_definition);
+        let build_timeout = project_definition.build_timeout.unwrap_or(DEFAULT_BUILD_TIMEOUT);
+        let deadline = Instant::now() + Duration::from_secs(build_timeout);
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        for command in commands.iter() {
+            // own process group, so processes started by the build can be killed along with it
+            let mut build_cmd = Popen::create(command, PopenConfig {
+                stdout: Redirection::Pipe,
+                stderr: Redirection::Pipe,
+                cwd: Some(directory.clone().into()),
+                setpgid: true,
+                ..Default::default()
+            }).map_err(|err| CompileError::BuildCommandFailed(command[0].clone(), err))?;
+
+            let capture = build_cmd.communicate_start(None)
+                .limit_time(deadline.saturating_duration_since(Instant::now()))
+                .read();
+            let (out, err, timed_out) = match capture {
+                Ok(c) => (c.0, c.1, false),
+                Err(e) => {
+                    let timed_out = e.kind() == std::io::ErrorKind::TimedOut;
+                    (e.capture.0, e.capture.1, timed_out)
+                },
+            };
+            stdout.push_str(&String::from_utf8_lossy(&out.unwrap_or_default()));
+            stderr.push_str(&String::from_utf8_lossy(&err.unwrap_or_default()));
+
+            if timed_out {
+                #[allow(unused_must_use)] // the build might have finished in the meantime
+                {
+                    build_cmd.send_signal_group(libc::SIGKILL);
+                    build_cmd.wait_timeout(Duration::from_secs(2));
+                }
+                return Err(CompileError::Timeout(build_timeout, Box::new(CompilationInfo {
+                    compiled: false,
+                    timed_out: true,
+                    errors: Some(format!("building timed out after {} seconds\n\n{}", build_timeout, stderr)),
+                    warnings: None,
+                    diagnostics: parse_diagnostics(&stderr),
+                    warning_penalty: 0.0,
+                    stdout: Some(stdout),
+                    stderr: Some(stderr),
+                })));
+            }
+
+            let exit_status = build_cmd.wait().map_err(|err| CompileError::BuildCommandFailed(command[0].clone(), err))?;
+            if !exit_status.success() {
+                return Ok(CompilationInfo {
+                    compiled: false,
+                    timed_out: false,
+                    errors: Some(stderr.clone()),
+                    warnings: None,
+                    diagnostics: parse_diagnostics(&stderr),
+                    warning_penalty: 0.0,
+                    stdout: Some(stdout),
+                    stderr: Some(stderr),
+                });
+            }
         }
 
-        let mut make_cmd = Command::new("make");
-        make_cmd.current_dir(makefile_path);
-        make_cmd.stderr(Stdio::piped());
-        make_cmd.stdout(Stdio::piped());
-        make_cmd.args(project_definition.make_targets.clone().unwrap_or(vec![]));
+        //checking for warnings...
+        let diagnostics = parse_diagnostics(&stderr);
+        let warns = count_warnings(&diagnostics);
+        let (warning_penalty, forbidden) = match project_definition.warning_policy.as_ref() {
+            Some(policy) => evaluate_warnings(policy, &warns),
+            None => (0.0, vec![]),
+        };
+        if !forbidden.is_empty() {
+            let errors = format!("forbidden compiler warnings: {}\n\n{}",
+                forbidden.iter().map(|warn| format!("-W{}", warn)).collect::<Vec<String>>().join(", "), stderr);
+            return Ok(CompilationInfo {
+                compiled: false,
+                timed_out: false,
+                errors: Some(errors),
+                warnings: Some(warns),
+                diagnostics,
+                warning_penalty: 0.0,
+                stdout: Some(stdout),
+                stderr: Some(stderr),
+            });
+        }
+
+        options.log("Compilation successful!");
 
         let mut warnings: Option<HashMap<String, i32>> = None;
-        match make_cmd.output() {
-            Ok(res) => {
-                let errors = String::from_utf8(res.stderr).unwrap_or_default();
-                let re_warnings = Regex::new(r"warning: .*? \[-W(?P<warn>[^\]]+)\]").unwrap();
-                if res.status.code().unwrap_or(-1) != 0 {
-                    Ok(CompilationInfo{ compiled: false, errors: Some(errors), warnings })
-                }
-                else {
-                    println!("Compilation successful!");
-
-                    //checking for warnings...
-                    let mut warns = HashMap::<String, i32>::new();
-                    for cap in re_warnings.captures_iter(&errors) {
-                        let warn = String::from(&cap["warn"]);
-                        let entry = warns.entry(warn).or_insert(0);
-                        *entry += 1;
-                    }
-                    if !warns.is_empty() {
-                        println!("Detected compiler warnings:");
-                        for (warn, amount) in warns.iter_mut() {
-                            println!("  {}: {}", warn, *amount);
-                        }
-                        warnings = Some(warns);
-                    }
-                    Ok(CompilationInfo { compiled: true, errors: None, warnings })
-                }
+        if !warns.is_empty() {
+            options.log("Detected compiler warnings:");
+            for (warn, amount) in warns.iter() {
+                options.log(&format!("  {}: {}", warn, *amount));
             }
-            Err(err) => {
-                Err(CompileError::MakeFailed(err))
+            if warning_penalty > 0.0 {
+                options.log(&format!("Penalty for compiler warnings: {}", warning_penalty));
             }
+            warnings = Some(warns);
         }
+        Ok(CompilationInfo { compiled: true, timed_out: false, errors: None, warnings, diagnostics, warning_penalty, stdout: Some(stdout), stderr: Some(stderr) })
     }
 }
-
@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use serde_derive::Deserialize;
+
+use super::binary::CompileError;
+use super::definition::ProjectDefinition;
+
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum BuildKind {
+    #[serde(alias = "make")]
+    Make,
+    #[serde(alias = "cmake")]
+    CMake,
+    /// arbitrary commands, e.g. calling the compiler directly
+    #[serde(alias = "command")]
+    Command,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Build {
+    pub kind: BuildKind,
+    /// directory the build commands are run in, defaults to `makefile_path`
+    pub directory: Option<String>,
+    /// targets passed to `make`, or to `cmake --build`
+    pub targets: Option<Vec<String>>,
+    /// build directory of CMake, relative to `directory`
+    pub build_dir: Option<String>,
+    /// additional arguments for configuring with CMake
+    pub cmake_flags: Option<Vec<String>>,
+    /// commands run one after another, each given as program and arguments
+    pub commands: Option<Vec<Vec<String>>>,
+}
+
+impl Build {
+    /// The configured build, or a `make` build for the older `makefile_path` and `make_targets` options.
+    pub fn from_project_definition(project_definition: &ProjectDefinition) -> Option<Build> {
+        match (&project_definition.build, &project_definition.makefile_path) {
+            (Some(build), _) => Some(build.clone()),
+            (None, Some(_)) => Some(Build {
+                kind: BuildKind::Make,
+                directory: None,
+                targets: project_definition.make_targets.clone(),
+                build_dir: None,
+                cmake_flags: None,
+                commands: None,
+            }),
+            (None, None) => None,
+        }
+    }
+
+    pub fn directory(&self, project_definition: &ProjectDefinition) -> String {
+        self.directory.clone()
+            .or(project_definition.makefile_path.clone())
+            .unwrap_or(".".to_owned())
+    }
+
+    /// Commands to run for building, checking for the needed build files first.
+    pub fn commands(&self, project_definition: &ProjectDefinition) -> Result<Vec<Vec<String>>, CompileError> {
+        let directory = self.directory(project_definition);
+        let targets = self.targets.clone().unwrap_or(vec![]);
+        match self.kind {
+            BuildKind::Make => {
+                if !Path::new(&format!("{}/Makefile", &directory)).is_file() {
+                    return Err(CompileError::MakefileNotFound(directory));
+                }
+                let mut make = vec!["make".to_owned()];
+                make.extend(targets);
+                Ok(vec![make])
+            },
+            BuildKind::CMake => {
+                if !Path::new(&format!("{}/CMakeLists.txt", &directory)).is_file() {
+                    return Err(CompileError::CMakeListsNotFound(directory));
+                }
+                let build_dir = self.build_dir.clone().unwrap_or("build".to_owned());
+                let mut configure = vec!["cmake".to_owned(), "-S".to_owned(), ".".to_owned(), "-B".to_owned(), build_dir.clone()];
+                configure.extend(self.cmake_flags.clone().unwrap_or(vec![]));
+                let mut build = vec!["cmake".to_owned(), "--build".to_owned(), build_dir];
+                if !targets.is_empty() {
+                    build.push("--target".to_owned());
+                    build.extend(targets);
+                }
+                Ok(vec![configure, build])
+            },
+            BuildKind::Command => {
+                let commands = self.commands.clone().unwrap_or(vec![]);
+                if commands.is_empty() || commands.iter().any(|command| command.is_empty()) {
+                    return Err(CompileError::NoBuildCommands);
+                }
+                Ok(commands)
+            },
+        }
+    }
+}
@@ -1,15 +1,37 @@
 use serde_derive::Deserialize;
 
+use crate::test::criteria::PassCriteria;
+use crate::test::normalize::Normalization;
+use crate::test::scoring::Scoring;
+use super::build::Build;
+use super::source_checks::SourceCheck;
+use super::warning_policy::{WarningPolicy, deserialize_warning_policy};
+
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ProjectDefinition {
     pub binary_path: String,
     pub makefile_path: Option<String>,
     pub make_targets: Option<Vec<String>>,
+    pub build: Option<Build>,
+    pub build_timeout: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_warning_policy")]
+    pub warning_policy: Option<WarningPolicy>,
+    pub source_checks: Option<Vec<SourceCheck>>,
     pub global_timeout : Option<u64>,
     pub valgrind_flags : Option<Vec<String>>,
     pub valgrind_log_folder: Option<String>,
     pub diff_table_width: Option<u64>,
     pub use_valgrind: Option<bool>,
+    pub retries: Option<u32>,
+    pub sandbox: Option<bool>,
+    pub fixtures: Option<Vec<String>>,
+    pub normalize: Option<Vec<Normalization>>,
+    pub scoring: Option<Scoring>,
+    pub pass_criteria: Option<PassCriteria>,
+    pub rlimit_memory: Option<u64>,
+    pub rlimit_cpu: Option<u64>,
+    pub rlimit_fsize: Option<u64>,
+    pub rlimit_nproc: Option<u64>,
 }
 
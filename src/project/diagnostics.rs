@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A single message of the compiler, usually located in the compiled sources.
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostic {
+    /// unset for messages of the compiler driver or linker, like `cc1: warning: ...`
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    /// the option enabling this diagnostic, like `-Wunused-variable`
+    pub flag: Option<String>,
+    pub message: String,
+}
+
+// format of `-fdiagnostics-format=json` of GCC
+#[derive(Deserialize)]
+struct JsonDiagnostic {
+    kind: String,
+    message: String,
+    option: Option<String>,
+    #[serde(default)]
+    locations: Vec<JsonLocation>,
+    #[serde(default)]
+    children: Vec<JsonDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct JsonLocation {
+    caret: JsonPosition,
+}
+
+#[derive(Deserialize)]
+struct JsonPosition {
+    file: String,
+    line: u32,
+    column: Option<u32>,
+}
+
+lazy_static! {
+    static ref RE_DIAGNOSTIC: Regex = Regex::new(
+        r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?:(?P<column>\d+):)? (?P<severity>fatal error|error|warning|note): (?P<message>.*?)(?: \[(?P<flag>-W[^\]]+)\])?$"
+    ).unwrap();
+    // messages without a location, like `cc1: warning: ... [-Wflag]`
+    static ref RE_TOOL_DIAGNOSTIC: Regex = Regex::new(
+        r"^[^:\s]+: (?P<severity>fatal error|error|warning|note): (?P<message>.*?)(?: \[(?P<flag>-W[^\]]+)\])?$"
+    ).unwrap();
+}
+
+/// Parses the diagnostics of GCC and Clang, given as text or as JSON (one array per line).
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for line in output.lines() {
+        let line = line.trim_end();
+        if line.starts_with('[') {
+            if let Ok(json) = serde_json::from_str::<Vec<JsonDiagnostic>>(line) {
+                json.iter().for_each(|diagnostic| push_json_diagnostic(diagnostic, &mut diagnostics));
+                continue;
+            }
+        }
+        if let Some(cap) = RE_DIAGNOSTIC.captures(line) {
+            diagnostics.push(Diagnostic {
+                file: Some(cap["file"].to_owned()),
+                line: cap["line"].parse().ok(),
+                column: cap.name("column").and_then(|column| column.as_str().parse().ok()),
+                severity: severity_from_str(&cap["severity"]),
+                flag: cap.name("flag").map(|flag| flag.as_str().to_owned()),
+                message: cap["message"].to_owned(),
+            });
+        }
+        else if let Some(cap) = RE_TOOL_DIAGNOSTIC.captures(line) {
+            diagnostics.push(Diagnostic {
+                file: None,
+                line: None,
+                column: None,
+                severity: severity_from_str(&cap["severity"]),
+                flag: cap.name("flag").map(|flag| flag.as_str().to_owned()),
+                message: cap["message"].to_owned(),
+            });
+        }
+    }
+    diagnostics
+}
+
+fn push_json_diagnostic(json: &JsonDiagnostic, diagnostics: &mut Vec<Diagnostic>) {
+    let location = json.locations.first().map(|location| &location.caret);
+    diagnostics.push(Diagnostic {
+        file: location.map(|location| location.file.clone()),
+        line: location.map(|location| location.line),
+        column: location.and_then(|location| location.column),
+        severity: severity_from_str(&json.kind),
+        flag: json.option.clone(),
+        message: json.message.clone(),
+    });
+    json.children.iter().for_each(|child| push_json_diagnostic(child, diagnostics));
+}
+
+fn severity_from_str(severity: &str) -> Severity {
+    match severity {
+        "warning" => Severity::Warning,
+        "note" => Severity::Note,
+        _ => Severity::Error,
+    }
+}
+
+/// Counts the warnings per flag, with the `-W` prefix removed.
+pub fn count_warnings(diagnostics: &[Diagnostic]) -> HashMap<String, i32> {
+    let mut warns = HashMap::<String, i32>::new();
+    for diagnostic in diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning) {
+        if let Some(flag) = diagnostic.flag.as_ref() {
+            let entry = warns.entry(flag.trim_start_matches("-W").to_owned()).or_insert(0);
+            *entry += 1;
+        }
+    }
+    warns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_diagnostics_are_parsed() {
+        let output = "main.c: In function 'main':\n\
+            main.c:4:9: warning: unused variable 'x' [-Wunused-variable]\n\
+            \x20   4 |     int x;\n\
+            main.c:7: error: expected ';' before '}' token\n";
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file.as_deref(), Some("main.c"));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(4), Some(9)));
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].flag.as_deref(), Some("-Wunused-variable"));
+        assert_eq!(diagnostics[0].message, "unused variable 'x'");
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (Some(7), None));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].flag, None);
+    }
+
+    #[test]
+    fn text_diagnostics_without_location_are_parsed() {
+        let diagnostics = parse_diagnostics("cc1: warning: command-line option '-Wfoo' is valid for C++ [-Wunused-command-line-argument]\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].file.as_ref(), diagnostics[0].line, diagnostics[0].column), (None, None, None));
+        assert_eq!(diagnostics[0].flag.as_deref(), Some("-Wunused-command-line-argument"));
+    }
+
+    #[test]
+    fn json_diagnostics_are_parsed() {
+        let output = r#"[{"kind": "warning", "message": "unused variable 'x'", "option": "-Wunused-variable", "locations": [{"caret": {"file": "main.c", "line": 4, "column": 9}}], "children": [{"kind": "note", "message": "declared here", "locations": [{"caret": {"file": "main.h", "line": 2}}]}]}]"#;
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file.as_deref(), Some("main.c"));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(4), Some(9)));
+        assert_eq!(diagnostics[0].flag.as_deref(), Some("-Wunused-variable"));
+        assert_eq!(diagnostics[1].severity, Severity::Note);
+        assert_eq!((diagnostics[1].file.as_deref(), diagnostics[1].line, diagnostics[1].column), (Some("main.h"), Some(2), None));
+    }
+
+    #[test]
+    fn json_diagnostics_without_location_are_kept() {
+        let output = r#"[{"kind": "warning", "message": "command-line option is not valid", "option": "-Wunused-command-line-argument", "locations": []}]"#;
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].file.as_ref(), diagnostics[0].line, diagnostics[0].column), (None, None, None));
+        assert_eq!(count_warnings(&diagnostics).get("unused-command-line-argument"), Some(&1));
+    }
+}
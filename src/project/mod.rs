@@ -1,3 +1,7 @@
 pub mod definition;
 pub mod binary;
+pub mod build;
+pub mod diagnostics;
+pub mod source_checks;
+pub mod warning_policy;
 
@@ -0,0 +1,266 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::util::walk_dir;
+
+
+#[derive(Debug, Error)]
+pub enum SourceCheckError {
+    #[error("invalid pattern in source checks `{0}`: {1}")]
+    InvalidPattern(String, regex::Error),
+    #[error("invalid glob in source checks `{0}`: {1}")]
+    InvalidGlob(String, String),
+}
+
+/// Patterns checked in the source files, before running any testcases.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SourceCheck {
+    /// globs selecting the files to check, relative to the project directory
+    pub files: Vec<String>,
+    /// regexes which must not match anywhere in the files
+    pub forbidden: Option<Vec<String>>,
+    /// regexes which must match somewhere in every file
+    pub required: Option<Vec<String>>,
+    /// whether violations fail all testcases, defaults to `false`
+    pub fail: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViolationKind {
+    Forbidden,
+    Required,
+}
+
+impl std::fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ViolationKind::Forbidden => write!(f, "forbidden"),
+            ViolationKind::Required => write!(f, "required"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    /// path relative to the project directory
+    pub file: String,
+    /// line of the match, only for forbidden patterns
+    pub line: Option<usize>,
+    pub kind: ViolationKind,
+    pub pattern: String,
+    /// whether this violation fails all testcases
+    pub fail: bool,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.kind, self.line) {
+            (ViolationKind::Forbidden, Some(line)) => write!(f, "{}:{}: forbidden pattern `{}`", self.file, line, self.pattern),
+            (ViolationKind::Forbidden, None) => write!(f, "{}: forbidden pattern `{}`", self.file, self.pattern),
+            (ViolationKind::Required, _) => write!(f, "{}: missing required pattern `{}`", self.file, self.pattern),
+        }
+    }
+}
+
+/// Runs all checks on the files below `dir`, returning the violations sorted by file.
+pub fn run_source_checks(checks: &[SourceCheck], dir: &str) -> Result<Vec<Violation>, SourceCheckError> {
+    let mut files = vec![];
+    walk_dir(Path::new(dir), &mut |path, _| {
+        if let Ok(relative) = path.strip_prefix(dir) {
+            files.push(relative.to_string_lossy().into_owned());
+        }
+    });
+    files.sort();
+
+    let mut violations = vec![];
+    for check in checks.iter() {
+        let globs = check.files.iter().map(|glob| glob_to_regex(glob)).collect::<Result<Vec<Regex>, SourceCheckError>>()?;
+        let forbidden = compile_patterns(check.forbidden.as_ref())?;
+        let required = compile_patterns(check.required.as_ref())?;
+        let fail = check.fail.unwrap_or(false);
+
+        for file in files.iter().filter(|file| globs.iter().any(|glob| glob.is_match(file))) {
+            // binary files and files not readable are skipped
+            let content = match read_to_string(Path::new(dir).join(file)) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            for (pattern, re) in forbidden.iter() {
+                for m in re.find_iter(&content) {
+                    violations.push(Violation {
+                        file: file.clone(),
+                        line: Some(content[..m.start()].matches('\n').count() + 1),
+                        kind: ViolationKind::Forbidden,
+                        pattern: pattern.clone(),
+                        fail,
+                    });
+                }
+            }
+            for (pattern, re) in required.iter() {
+                if !re.is_match(&content) {
+                    violations.push(Violation { file: file.clone(), line: None, kind: ViolationKind::Required, pattern: pattern.clone(), fail });
+                }
+            }
+        }
+    }
+    violations.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(violations)
+}
+
+fn compile_patterns(patterns: Option<&Vec<String>>) -> Result<Vec<(String, Regex)>, SourceCheckError> {
+    patterns.map(|patterns| patterns.as_slice()).unwrap_or(&[]).iter().map(|pattern| {
+        // multiline-mode, so `^` and `$` match the beginning and end of a line
+        RegexBuilder::new(pattern).multi_line(true).build()
+            .map(|re| (pattern.clone(), re))
+            .map_err(|err| SourceCheckError::InvalidPattern(pattern.clone(), err))
+    }).collect()
+}
+
+/// Converts a glob into a regex on relative paths: `*` and `?` do not match `/`, while `**/` matches any directories.
+/// Classes like `[abc]`, `[a-z]` or `[!abc]` match a single character, alternatives like `{c,h}` one of the globs given.
+fn glob_to_regex(glob: &str) -> Result<Regex, SourceCheckError> {
+    let invalid = |reason: &str| SourceCheckError::InvalidGlob(glob.to_owned(), reason.to_owned());
+    let mut re = String::from("^");
+    let mut alternatives = 0;
+    let mut chars = glob.trim_start_matches("./").chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                }
+                else {
+                    re.push_str(".*");
+                }
+            },
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let negated = chars.next_if(|c| *c == '!' || *c == '^').is_some();
+                // a `]` right at the start belongs to the class
+                let mut class = chars.next_if_eq(&']').into_iter().collect::<Vec<char>>();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => class.push(c),
+                        None => return Err(invalid("unclosed `[`")),
+                    }
+                }
+                re.push_str(if negated { "[^/" } else { "[" });
+                for (i, c) in class.iter().enumerate() {
+                    if *c == '-' && i > 0 && i < class.len() - 1 {
+                        re.push('-');
+                    }
+                    else {
+                        re.push_str(&regex::escape(&c.to_string()));
+                    }
+                }
+                re.push(']');
+            },
+            '{' => {
+                alternatives += 1;
+                re.push_str("(?:");
+            },
+            ',' if alternatives > 0 => re.push('|'),
+            '}' if alternatives > 0 => {
+                alternatives -= 1;
+                re.push(')');
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if alternatives > 0 {
+        return Err(invalid("unclosed `{`"));
+    }
+    re.push('$');
+    // only reversed ranges like `[z-a]` are left to fail
+    Regex::new(&re).map_err(|_| invalid("invalid range in `[...]`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        glob_to_regex(glob).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn wildcards_stay_within_directories() {
+        assert!(matches("*.c", "main.c"));
+        assert!(!matches("*.c", "src/main.c"));
+        assert!(matches("./src/?.c", "src/a.c"));
+        assert!(!matches("src/?.c", "src/ab.c"));
+        assert!(matches("src/**/*.h", "src/a.h"));
+        assert!(matches("src/**/*.h", "src/lib/util/a.h"));
+        assert!(!matches("src/**/*.h", "include/a.h"));
+        assert!(matches("main.c", "main.c"));
+        assert!(!matches("main.c", "mainxc"));
+    }
+
+    #[test]
+    fn classes_match_single_characters() {
+        assert!(matches("[abc].c", "b.c"));
+        assert!(!matches("[abc].c", "d.c"));
+        assert!(matches("test[0-9].c", "test7.c"));
+        assert!(!matches("test[0-9].c", "testx.c"));
+        assert!(matches("[!a].c", "b.c"));
+        assert!(!matches("[!a].c", "a.c"));
+        assert!(!matches("x[!a]y", "x/y"));
+        assert!(matches("[]-].c", "].c"));
+        assert!(matches("[]-].c", "-.c"));
+    }
+
+    #[test]
+    fn alternatives_match_any_glob() {
+        assert!(matches("*.{c,h}", "main.c"));
+        assert!(matches("*.{c,h}", "main.h"));
+        assert!(!matches("*.{c,h}", "main.o"));
+        assert!(matches("{src,include}/**/*.{c,cpp}", "include/x/y.cpp"));
+        assert!(matches("a,b.c", "a,b.c"));
+        assert!(matches("a}.c", "a}.c"));
+    }
+
+    #[test]
+    fn invalid_globs_are_rejected() {
+        assert!(glob_to_regex("[abc.c").is_err());
+        assert!(glob_to_regex("*.{c,h").is_err());
+        assert!(glob_to_regex("[z-a].c").is_err());
+    }
+
+    #[test]
+    fn violations_are_reported_with_lines() {
+        let dir = std::env::temp_dir().join(format!("testrunner-source-checks-{}", Uuid::new_v4().to_simple()));
+        create_dir_all(dir.join("src")).unwrap();
+        write(dir.join("main.c"), "int main() {\n  system(\"ls\");\n\n  return system(\"pwd\");\n}\n").unwrap();
+        write(dir.join("src/util.c"), "// system\n").unwrap();
+        write(dir.join("notes.txt"), "system\n").unwrap();
+        let checks = vec![SourceCheck {
+            files: vec!["**/*.c".to_owned()],
+            forbidden: Some(vec![r"\bsystem\b".to_owned()]),
+            required: Some(vec!["^int main".to_owned()]),
+            fail: Some(true),
+        }];
+        let violations = run_source_checks(&checks, &dir.to_string_lossy());
+        remove_dir_all(&dir).unwrap();
+
+        let violations = violations.unwrap().iter().map(|violation| violation.to_string()).collect::<Vec<String>>();
+        assert_eq!(violations, vec![
+            "main.c:2: forbidden pattern `\\bsystem\\b`",
+            "main.c:4: forbidden pattern `\\bsystem\\b`",
+            "src/util.c: missing required pattern `^int main`",
+            "src/util.c:1: forbidden pattern `\\bsystem\\b`",
+        ]);
+    }
+}
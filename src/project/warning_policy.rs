@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+
+use crate::test::scoring::round_score;
+
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum WarningAction {
+    /// the build fails, so no testcases are run
+    #[serde(alias = "fail")]
+    Fail,
+}
+
+/// How a compiler warning is treated, given either as penalty or as action.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WarningRule {
+    /// points deducted from the total score per occurrence
+    Penalty(f32),
+    Action(WarningAction),
+}
+
+/// Rules for compiler warnings, by flag without the `-W` prefix.
+pub type WarningPolicy = HashMap<String, WarningRule>;
+
+/// Deserializes the rules by flag, which may be given with or without the `-W` prefix, but only once.
+pub fn deserialize_warning_policy<'de, D>(deserializer: D) -> Result<Option<WarningPolicy>, D::Error>
+    where D: Deserializer<'de>
+{
+    let rules = match Option::<HashMap<String, WarningRule>>::deserialize(deserializer)? {
+        Some(rules) => rules,
+        None => return Ok(None),
+    };
+    let mut policy = WarningPolicy::new();
+    for (flag, rule) in rules {
+        let flag = flag.trim_start_matches("-W").to_owned();
+        if policy.insert(flag.clone(), rule).is_some() {
+            return Err(serde::de::Error::custom(format!("rule for warning given twice: {}", flag)));
+        }
+    }
+    Ok(Some(policy))
+}
+
+/// Total penalty and the forbidden warnings found, given the warnings counted per flag.
+pub fn evaluate_warnings(policy: &WarningPolicy, warnings: &HashMap<String, i32>) -> (f32, Vec<String>) {
+    let mut penalty = 0.0;
+    let mut forbidden = vec![];
+    for (flag, rule) in policy.iter() {
+        let amount = warnings.get(flag).cloned().unwrap_or(0);
+        if amount == 0 {
+            continue;
+        }
+        match rule {
+            WarningRule::Penalty(points) => penalty += points * amount as f32,
+            WarningRule::Action(WarningAction::Fail) => forbidden.push(flag.clone()),
+        }
+    }
+    forbidden.sort();
+    (round_score(penalty), forbidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        #[serde(default, deserialize_with = "deserialize_warning_policy")]
+        warning_policy: Option<WarningPolicy>,
+    }
+
+    fn parse(config: &str) -> Result<Option<WarningPolicy>, toml::de::Error> {
+        toml::from_str::<Config>(config).map(|config| config.warning_policy)
+    }
+
+    #[test]
+    fn flags_are_normalized() {
+        let policy = parse("[warning_policy]\n\"-Wunused-variable\" = 0.5\nshadow = \"fail\"\n").unwrap().unwrap();
+        let mut flags = policy.keys().cloned().collect::<Vec<String>>();
+        flags.sort();
+        assert_eq!(flags, vec!["shadow", "unused-variable"]);
+        assert!(parse("").unwrap().is_none());
+    }
+
+    #[test]
+    fn flags_given_twice_are_rejected() {
+        assert!(parse("[warning_policy]\n\"-Wunused-variable\" = 0.5\nunused-variable = 1\n").is_err());
+    }
+
+    #[test]
+    fn warnings_are_charged_once_per_occurrence() {
+        let policy = parse("[warning_policy]\n\"-Wunused-variable\" = 0.5\nshadow = \"fail\"\nformat = 1\n").unwrap().unwrap();
+        let warnings = vec![("unused-variable".to_owned(), 3), ("shadow".to_owned(), 1)].into_iter().collect();
+        assert_eq!(evaluate_warnings(&policy, &warnings), (1.5, vec!["shadow".to_owned()]));
+    }
+}
@@ -0,0 +1,65 @@
+use serde_derive::{Deserialize, Serialize};
+
+
+/// Conditions a testcase has to meet to pass. Unset criteria fall back to the project's criteria, then to the defaults.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PassCriteria {
+    /// minimal similarity of output and error output, defaults to an exact match
+    pub min_output_similarity: Option<f32>,
+    /// minimal similarity of the additional file, defaults to an exact match
+    pub min_file_similarity: Option<f32>,
+    pub require_exit_code: Option<bool>,
+    pub max_mem_leaks: Option<i32>,
+    pub max_mem_errors: Option<i32>,
+}
+
+/// The results of a testcase, as far as relevant for the pass criteria.
+pub struct Evaluation {
+    pub exit_code_correct: bool,
+    pub timeout: bool,
+    /// a resource limit was exceeded, see `ResourceLimits::exceeded`
+    pub limit_exceeded: bool,
+    pub distance: f32,
+    pub stderr_distance: Option<f32>,
+    pub add_distance: f32,
+    pub mem_leaks: Option<i32>,
+    pub mem_errors: Option<i32>,
+}
+
+impl PassCriteria {
+    pub fn merge(testcase: Option<&PassCriteria>, project: Option<&PassCriteria>) -> PassCriteria {
+        let testcase = testcase.cloned().unwrap_or_default();
+        let project = project.cloned().unwrap_or_default();
+        PassCriteria {
+            min_output_similarity: testcase.min_output_similarity.or(project.min_output_similarity),
+            min_file_similarity: testcase.min_file_similarity.or(project.min_file_similarity),
+            require_exit_code: testcase.require_exit_code.or(project.require_exit_code),
+            max_mem_leaks: testcase.max_mem_leaks.or(project.max_mem_leaks),
+            max_mem_errors: testcase.max_mem_errors.or(project.max_mem_errors),
+        }
+    }
+
+    pub fn evaluate(&self, evaluation: &Evaluation) -> bool {
+        if evaluation.timeout || evaluation.limit_exceeded {
+            return false;
+        }
+
+        let min_output_similarity = self.min_output_similarity.unwrap_or(1.0);
+        if evaluation.distance < min_output_similarity || evaluation.stderr_distance.unwrap_or(1.0) < min_output_similarity {
+            return false;
+        }
+        if evaluation.add_distance < self.min_file_similarity.unwrap_or(1.0) {
+            return false;
+        }
+        if self.require_exit_code.unwrap_or(true) && !evaluation.exit_code_correct {
+            return false;
+        }
+
+        // valgrind criteria can't be checked without valgrind results
+        let exceeds = |max: Option<i32>, value: Option<i32>| match (max, value) {
+            (Some(max), Some(value)) => value > max,
+            _ => false,
+        };
+        !exceeds(self.max_mem_leaks, evaluation.mem_leaks) && !exceeds(self.max_mem_errors, evaluation.mem_errors)
+    }
+}
@@ -1,10 +1,13 @@
+use std::ops::Range;
 use std::time::{Duration, Instant};
 
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use serde_derive::Serialize;
-use similar::{Algorithm, ChangeTag, TextDiff, capture_diff_slices_deadline, get_diff_ratio};
+use similar::{Algorithm, ChangeTag, DiffOp, TextDiff, capture_diff_slices_deadline, get_diff_ratio};
+use similar::algorithms::{Capture, myers};
 
 use super::ordio_test::IODiff;
+use super::test::MatchMode;
 
 
 #[derive(Clone, Debug, Serialize)]
@@ -66,6 +69,98 @@ pub fn diff_plaintext(old: &str, new: &str, timeout: Duration) -> (Vec<Changeset
     (changeset, diff.ratio())
 }
 
+/// A line of the reference output, matched against a line of the given output using a regex.
+struct LinePattern {
+    regex: Regex,
+    newline: bool,
+}
+
+impl LinePattern {
+    fn new(line: &str, mode: MatchMode, ignore_case: bool) -> Result<Self, regex::Error> {
+        let newline = line.ends_with('\n');
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let pattern = match mode {
+            MatchMode::Exact => regex::escape(line),
+            MatchMode::LinesRegex => format!("(?:{})", line),
+            // literal text with `{{regex}}` placeholders
+            MatchMode::Regex => {
+                let mut pattern = String::new();
+                let mut last = 0;
+                for (range, regex) in placeholders(line) {
+                    pattern.push_str(&regex::escape(&line[last..range.start]));
+                    pattern.push_str(&format!("(?:{})", regex));
+                    last = range.end;
+                }
+                pattern.push_str(&regex::escape(&line[last..]));
+                pattern
+            },
+        };
+        let regex = RegexBuilder::new(&format!(r"\A{}\z", pattern))
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(LinePattern { regex, newline })
+    }
+}
+
+struct GivenLine<'a>(&'a str);
+
+impl PartialEq<LinePattern> for GivenLine<'_> {
+    fn eq(&self, other: &LinePattern) -> bool {
+        self.0.ends_with('\n') == other.newline
+            && other.regex.is_match(self.0.strip_suffix('\n').unwrap_or(self.0))
+    }
+}
+
+/// Finds the `{{regex}}` placeholders of a line, returning their ranges and regexes.
+/// A placeholder ends at the last `}}` of a run of closing braces, so regexes may end with a repetition like `\d{4}`.
+fn placeholders(line: &str) -> Vec<(Range<usize>, &str)> {
+    let mut placeholders = vec![];
+    let mut pos = 0;
+    while let Some(start) = line[pos..].find("{{").map(|i| pos + i) {
+        let close = match line[start + 2..].find("}}") {
+            Some(i) => start + 2 + i,
+            None => break,
+        };
+        let braces = line[close..].len() - line[close..].trim_start_matches('}').len();
+        let end = close + braces;
+        placeholders.push((start..end, &line[start + 2..end - 2]));
+        pos = end;
+    }
+    placeholders
+}
+
+/// Like `diff_plaintext`, but lines of the reference may contain regexes, depending on `mode`.
+/// Reference lines matching a line of the given output are replaced by that line before diffing,
+/// so they show up as unchanged, while mismatching lines are diffed as usual.
+/// As the reference can't be lowercased without altering its regexes, `ignore_case` makes them match case-insensitively.
+pub fn diff_plaintext_matching(old: &str, new: &str, mode: MatchMode, ignore_case: bool, timeout: Duration) -> Result<(Vec<ChangesetInline<String>>, f32), regex::Error> {
+    if let MatchMode::Exact = mode {
+        return Ok(diff_plaintext(old, new, timeout));
+    }
+
+    let old_lines = old.split_inclusive('\n').collect::<Vec<&str>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<&str>>();
+    let patterns = old_lines.iter()
+        .map(|line| LinePattern::new(line, mode, ignore_case))
+        .collect::<Result<Vec<LinePattern>, regex::Error>>()?;
+    let given = new_lines.iter().map(|line| GivenLine(line)).collect::<Vec<GivenLine>>();
+
+    let mut capture = Capture::new();
+    myers::diff_deadline(&mut capture, &patterns, 0..patterns.len(), &given, 0..given.len(), Some(Instant::now() + timeout))
+        .unwrap_or(()); // `Capture` never fails
+
+    let mut substituted = old_lines.iter().map(|line| line.to_string()).collect::<Vec<String>>();
+    for op in capture.into_ops() {
+        if let DiffOp::Equal { old_index, new_index, len } = op {
+            for i in 0..len {
+                substituted[old_index + i] = new_lines[new_index + i].to_string();
+            }
+        }
+    }
+
+    Ok(diff_plaintext(&substituted.concat(), new, timeout))
+}
+
 pub fn diff_binary(old: &[u8], new: &[u8], timeout: Duration) -> (Vec<ChangesetFlat<Vec<u8>>>, f32) {
     let diff = capture_diff_slices_deadline(
         Algorithm::Patience,
@@ -105,6 +200,21 @@ pub fn textdiff_to_html(changeset: &Vec<ChangesetInline<String>>, ws_hints: bool
     let mut diff_left = String::new();
     let mut diff_right = String::new();
 
+    push_textdiff_html(changeset, ws_hints, &mut diff_left, &mut diff_right);
+
+    if ws_hints {
+        diff_left = diff_left.replace("\n", "&#x21b5;<br />").replace("\0", "&#x2205;<br />");
+        diff_right = diff_right.replace("\n", "&#x21b5;<br />").replace("\0", "&#x2205;<br />");
+    }
+    else {
+        diff_left = diff_left.replace("\n", "<br />").replace("\0", "<br />");
+        diff_right = diff_right.replace("\n", "<br />").replace("\0", "<br />");
+    }
+
+    (diff_left, diff_right)
+}
+
+fn push_textdiff_html(changeset: &[ChangesetInline<String>], ws_hints: bool, diff_left: &mut String, diff_right: &mut String) {
     changeset.iter().for_each(|change| {
         match change {
             ChangesetInline::Same(line) => {
@@ -142,17 +252,6 @@ pub fn textdiff_to_html(changeset: &Vec<ChangesetInline<String>>, ws_hints: bool
             },
         }
     });
-
-    if ws_hints {
-        diff_left = diff_left.replace("\n", "&#x21b5;<br />").replace("\0", "&#x2205;<br />");
-        diff_right = diff_right.replace("\n", "&#x21b5;<br />").replace("\0", "&#x2205;<br />");
-    }
-    else {
-        diff_left = diff_left.replace("\n", "<br />").replace("\0", "<br />");
-        diff_right = diff_right.replace("\n", "<br />").replace("\0", "<br />");
-    }
-
-    (diff_left, diff_right)
 }
 
 fn binarydata_to_hexdump(data: &[u8], offset: &mut usize, num_lines: &mut isize) -> String {
@@ -252,44 +351,15 @@ pub fn iodiff_to_html(changeset: &[IODiff], ws_hints: bool) -> (String, String)
                 diff_left.push_str(&format!("<span class=\"diff-input-unsent\">{}</span>", &with_ws_hints(input, ws_hints)));
             },
             IODiff::Output(changes) => {
-                changes.iter().for_each(|change| {
-                    match change {
-                        ChangesetInline::Same(line) => {
-                            line.iter().for_each(|segment| {
-                                match segment {
-                                    ChangesetFlat::Same(text) => {
-                                        diff_left.push_str(&with_ws_hints(text, ws_hints));
-                                        diff_right.push_str(&with_ws_hints(text, ws_hints));
-                                    },
-                                    _ => {}, // ChangesetInline::Same only contains ChangesetFlat::Same
-                                }
-                            });
-                        },
-                        ChangesetInline::Remove(line) => {
-                            diff_left.push_str("<span class=\"diff-add\">");
-                            line.iter().for_each(|segment| {
-                                match segment {
-                                    ChangesetFlat::Same(text) => diff_left.push_str(&with_ws_hints(text, ws_hints)),
-                                    ChangesetFlat::Remove(text) => diff_left.push_str(&format!("<span class=\"diff-add-inline\">{}</span>", &with_ws_hints(text, ws_hints))),
-                                    _ => {},
-                                }
-                            });
-                            diff_left.push_str("</span>");
-                        },
-                        ChangesetInline::Add(line) => {
-                            diff_right.push_str("<span class=\"diff-remove\">");
-                            line.iter().for_each(|segment| {
-                                match segment {
-                                    ChangesetFlat::Same(text) => diff_right.push_str(&with_ws_hints(text, ws_hints)),
-                                    ChangesetFlat::Add(text) => diff_right.push_str(&format!("<span class=\"diff-remove-inline\">{}</span>", &with_ws_hints(text, ws_hints))),
-                                    _ => {},
-                                }
-                            });
-                            diff_right.push_str("</span>");
-                        },
-                    }
-                });
-            }
+                push_textdiff_html(changes, ws_hints, &mut diff_left, &mut diff_right);
+            },
+            IODiff::ErrOutput(changes) => {
+                diff_left.push_str("<span class=\"diff-stderr\">");
+                diff_right.push_str("<span class=\"diff-stderr\">");
+                push_textdiff_html(changes, ws_hints, &mut diff_left, &mut diff_right);
+                diff_left.push_str("</span>");
+                diff_right.push_str("</span>");
+            },
         }
     });
 
@@ -305,3 +375,172 @@ pub fn iodiff_to_html(changeset: &[IODiff], ws_hints: bool) -> (String, String)
     (diff_left, diff_right)
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum UnifiedTag {
+    Same,
+    Remove,
+    Add,
+}
+
+/// A line of a unified diff, split into segments which are emphasized if they changed within the line.
+type UnifiedLine = (UnifiedTag, Vec<(bool, String)>);
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_CYAN: &str = "\x1b[36m";
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_REVERSE: &str = "\x1b[7m";
+const ANSI_NO_REVERSE: &str = "\x1b[27m";
+
+fn push_unified_lines(changeset: &[ChangesetInline<String>], lines: &mut Vec<UnifiedLine>) {
+    changeset.iter().for_each(|change| {
+        let (tag, line) = match change {
+            ChangesetInline::Same(line) => (UnifiedTag::Same, line),
+            ChangesetInline::Remove(line) => (UnifiedTag::Remove, line),
+            ChangesetInline::Add(line) => (UnifiedTag::Add, line),
+        };
+        let segments = line.iter().map(|segment| {
+            match segment {
+                ChangesetFlat::Same(text) => (false, text.clone()),
+                ChangesetFlat::Add(text) | ChangesetFlat::Remove(text) => (true, text.clone()),
+            }
+        }).collect();
+        lines.push((tag, segments));
+    });
+}
+
+/// Renders lines as unified diff hunks with `context` unchanged lines around each change.
+/// With `color`, ANSI escape codes are added and changes within a line are highlighted.
+fn lines_to_unified(lines: &[UnifiedLine], context: usize, color: bool) -> String {
+    let paint = |code: &str, text: &str| if color { format!("{}{}{}", code, text, ANSI_RESET) } else { text.to_owned() };
+
+    let changed = lines.iter().enumerate()
+        .filter(|(_, (tag, _))| *tag != UnifiedTag::Same)
+        .map(|(i, _)| i)
+        .collect::<Vec<usize>>();
+
+    // merge changes into hunks, if their context overlaps
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for i in changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut unified = format!("{}\n{}\n", paint(ANSI_BOLD, "--- expected"), paint(ANSI_BOLD, "+++ actual"));
+    for (start, end) in hunks {
+        let old_start = lines[..start].iter().filter(|(tag, _)| *tag != UnifiedTag::Add).count();
+        let new_start = lines[..start].iter().filter(|(tag, _)| *tag != UnifiedTag::Remove).count();
+        let old_len = lines[start..end].iter().filter(|(tag, _)| *tag != UnifiedTag::Add).count();
+        let new_len = lines[start..end].iter().filter(|(tag, _)| *tag != UnifiedTag::Remove).count();
+        // empty ranges start at the line before them
+        let old_start = if old_len > 0 { old_start + 1 } else { old_start };
+        let new_start = if new_len > 0 { new_start + 1 } else { new_start };
+        unified.push_str(&paint(ANSI_CYAN, &format!("@@ -{},{} +{},{} @@", old_start, old_len, new_start, new_len)));
+        unified.push('\n');
+
+        for (i, (tag, segments)) in lines.iter().enumerate().take(end).skip(start) {
+            let (prefix, code) = match tag {
+                UnifiedTag::Same => (" ", ""),
+                UnifiedTag::Remove => ("-", ANSI_RED),
+                UnifiedTag::Add => ("+", ANSI_GREEN),
+            };
+            let mut line = String::from(prefix);
+            let mut newline = false;
+            for (j, (emph, text)) in segments.iter().enumerate() {
+                let text = if j + 1 == segments.len() {
+                    newline = text.ends_with('\n');
+                    text.strip_suffix('\n').unwrap_or(text)
+                }
+                else {
+                    text
+                };
+                if color && *emph {
+                    line.push_str(&format!("{}{}{}", ANSI_REVERSE, text, ANSI_NO_REVERSE));
+                }
+                else {
+                    line.push_str(text);
+                }
+            }
+            unified.push_str(&if code.is_empty() { line } else { paint(code, &line) });
+            unified.push('\n');
+            // output of ordered IO tests may be followed by input, so only the last line is marked
+            if !newline && i + 1 == lines.len() {
+                unified.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    unified
+}
+
+pub fn textdiff_to_unified(changeset: &[ChangesetInline<String>], context: usize, color: bool) -> String {
+    let mut lines = vec![];
+    push_unified_lines(changeset, &mut lines);
+    lines_to_unified(&lines, context, color)
+}
+
+/// Input is shown as unchanged lines, as it is part of both transcripts.
+pub fn iodiff_to_unified(changeset: &[IODiff], context: usize, color: bool) -> String {
+    let mut lines = vec![];
+    changeset.iter().for_each(|io_diff| {
+        match io_diff {
+            IODiff::Input(input) | IODiff::InputUnsent(input) => {
+                input.split_inclusive('\n').for_each(|line| lines.push((UnifiedTag::Same, vec![(false, line.to_owned())])));
+            },
+            IODiff::Output(changes) | IODiff::ErrOutput(changes) => push_unified_lines(changes, &mut lines),
+        }
+    });
+    lines_to_unified(&lines, context, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(reference: &str, given: &str, mode: MatchMode) -> bool {
+        let (_, distance) = diff_plaintext_matching(reference, given, mode, false, Duration::from_secs(1)).unwrap();
+        distance == 1.0
+    }
+
+    #[test]
+    fn placeholders_end_at_last_closing_braces() {
+        assert_eq!(placeholders(r"year {{\d{4}}}!"), vec![(5..14, r"\d{4}")]);
+        assert_eq!(placeholders("{{a}} and {{b+}}"), vec![(0..5, "a"), (10..16, "b+")]);
+    }
+
+    #[test]
+    fn placeholders_keep_escaped_braces() {
+        assert_eq!(placeholders(r"{{a\}}}"), vec![(0..7, r"a\}")]);
+        assert!(matches("{{a\\}}}\n", "a}\n", MatchMode::Regex));
+        assert!(!matches("{{a\\}}}\n", "a\n", MatchMode::Regex));
+    }
+
+    #[test]
+    fn placeholders_need_closing_braces() {
+        assert!(placeholders("{{a} and {b}").is_empty());
+        assert!(matches("{{a}\n", "{{a}\n", MatchMode::Regex));
+    }
+
+    #[test]
+    fn regex_mode_escapes_text_around_placeholders() {
+        assert!(matches("took {{\\d+}}.{{\\d}}s\n", "took 12.5s\n", MatchMode::Regex));
+        assert!(!matches("took {{\\d+}}.{{\\d}}s\n", "took 1205s\n", MatchMode::Regex));
+        assert!(!matches("a.c\n", "abc\n", MatchMode::Regex));
+    }
+
+    #[test]
+    fn lines_regex_mode_matches_whole_lines() {
+        assert!(matches("a.c\n[0-9]+\n", "abc\n42\n", MatchMode::LinesRegex));
+        assert!(!matches("b\n", "abc\n", MatchMode::LinesRegex));
+    }
+
+    #[test]
+    fn invalid_regexes_are_errors() {
+        assert!(diff_plaintext_matching("{{(}}\n", "(\n", MatchMode::Regex, false, Duration::from_secs(1)).is_err());
+        assert!(diff_plaintext_matching("(\n", "(\n", MatchMode::LinesRegex, false, Duration::from_secs(1)).is_err());
+    }
+}
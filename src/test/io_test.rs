@@ -1,6 +1,7 @@
 use std::fs::{create_dir_all, Permissions, read_to_string, set_permissions};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::process::{Command, Stdio};
 use std::sync::Weak;
 use std::time::Duration;
@@ -14,9 +15,14 @@ use crate::project::binary::Binary;
 use crate::project::definition::ProjectDefinition;
 use crate::testresult::io_testresult::IoTestresult;
 use crate::testresult::testresult::Testresult;
-use crate::testrunner::{TestrunnerError, TestrunnerOptions};
-use super::test::{Test, TestMeta, TestcaseType, TestingError};
-use super::diff::diff_plaintext;
+use crate::testrunner::{Isolation, TestrunnerError, TestrunnerOptions};
+use super::test::{ExitOutcome, MatchMode, ResourceLimits, Test, TestMeta, TestcaseType, TestingError, deserialize_signal};
+use super::diff::diff_plaintext_matching;
+use super::launcher::{LaunchStatus, launcher_cmdline, status_filepath};
+use super::normalize::{Normalization, get_normalizations, normalize};
+use super::sandbox::Sandbox;
+use super::criteria::Evaluation;
+use super::scoring::similarity;
 
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -38,8 +44,14 @@ pub struct IoTest {
     #[serde(default)]
     exp_string: String,
     #[serde(default)]
+    match_mode: MatchMode,
+    exp_stderr_file: Option<String>,
+    exp_stderr_string: Option<String>,
+    #[serde(default)]
     argv: Vec<String>,
     exp_exit_code: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_signal")]
+    exp_signal: Option<u8>,
     env_vars: Option<Vec<String>>,
 }
 
@@ -73,27 +85,55 @@ impl Test for IoTest {
 
         let basedir = project_definition.makefile_path.clone().unwrap_or(".".to_owned());
         let (vg_log_folder, vg_filepath) = prepare_valgrind(&project_definition, &options, &self.meta, &basedir);
-        let (cmd_name, flags) = prepare_cmdline(&project_definition, &options, &vg_filepath, false)?;
+        let sandbox = prepare_sandbox(&project_definition, &options, &self.meta, &basedir)?;
+        let workdir = sandbox.as_ref().map(|s| s.path().to_string_lossy().into_owned()).unwrap_or(basedir.clone());
+        let limits = ResourceLimits::new(&project_definition, &self.meta);
+        let status_filepath = status_filepath();
+        let (cmd_name, flags) = prepare_cmdline(&project_definition, &options, &limits, sandbox.as_ref(), &vg_filepath, &status_filepath, false)?;
         let env_vars = prepare_envvars(self.env_vars.as_ref());
 
         let global_timeout = project_definition.global_timeout.unwrap_or(5);
         let timeout = self.meta.timeout.unwrap_or(global_timeout);
 
-        let (input, reference_output, mut given_output, exit_code) = self.run_command_with_timeout(&cmd_name, &flags, &env_vars, timeout)?;
-        let had_timeout = !exit_code.is_some();
-        let truncated_output;
-        if had_timeout && given_output.chars().count() > reference_output.chars().count() * 2 {
-            given_output.truncate(given_output.char_indices().nth(reference_output.chars().count() * 2).unwrap_or((512, ' ')).0);
-            truncated_output = true;
-        }
-        else {
-            truncated_output = false;
+        let mut reference_stderr = read_reference_stderr(self.exp_stderr_file.as_ref(), self.exp_stderr_string.as_ref())?;
+        let (input, mut reference_output, mut given_output, mut given_stderr, exit_outcome) = self.run_command_with_timeout(&cmd_name, &flags, &env_vars, &workdir, timeout)?;
+        // the launcher reports the wait status of the program itself, unless it was killed on a timeout
+        let launch_status = LaunchStatus::read(&status_filepath).filter(|_| !exit_outcome.had_timeout());
+        let exit_outcome = launch_status.as_ref().map_or(exit_outcome, |status| status.exit_outcome());
+        let had_timeout = exit_outcome.had_timeout();
+        let exit_code_correct = exit_outcome.matches(self.exp_exit_code, self.exp_signal) && !had_timeout;
+        let limit_exceeded = limits.exceeded(exit_outcome, exit_code_correct, launch_status.map(|status| status.cpu_time));
+        let mut truncated_output = false;
+        if had_timeout {
+            truncated_output |= truncate_output(&mut given_output, reference_output.chars().count());
+            if let Some(ref reference_stderr) = reference_stderr {
+                truncated_output |= truncate_output(&mut given_stderr, reference_stderr.chars().count());
+            }
         }
 
-        let (changeset, distance) = diff_plaintext(&reference_output, &given_output, Duration::from_secs(timeout));
-        let (add_diff, add_distance, add_file_missing) = self.get_add_diff()?;
+        let normalizations = get_normalizations(&project_definition, &self.meta);
+        let ignore_case = normalizations.contains(&Normalization::CaseInsensitive);
+        let ref_normalizations = match self.match_mode {
+            MatchMode::Exact => normalizations.clone(),
+            _ => normalizations.iter().cloned().filter(|e| *e != Normalization::CaseInsensitive).collect(),
+        };
+        let raw_output = if normalizations.is_empty() { None } else { Some(given_output.clone()) };
+        if !normalizations.is_empty() {
+            reference_output = normalize(&reference_output, &ref_normalizations);
+            given_output = normalize(&given_output, &normalizations);
+            reference_stderr = reference_stderr.map(|e| normalize(&e, &ref_normalizations));
+            given_stderr = normalize(&given_stderr, &normalizations);
+        }
 
-        let passed = self.did_pass(self.exp_exit_code, exit_code, distance, add_distance, had_timeout);
+        let (changeset, distance) = diff_plaintext_matching(&reference_output, &given_output, self.match_mode, ignore_case, Duration::from_secs(timeout))?;
+        let (stderr_diff, stderr_distance) = match reference_stderr {
+            Some(ref reference_stderr) => {
+                let (changeset, distance) = diff_plaintext_matching(reference_stderr, &given_stderr, self.match_mode, ignore_case, Duration::from_secs(timeout))?;
+                (Some(changeset), Some(distance))
+            },
+            None => (None, None),
+        };
+        let (add_diff, add_distance, add_file_missing) = self.get_add_diff(sandbox.as_ref())?;
 
         let (mem_leaks, mem_errors) = if had_timeout {
             (None, None)
@@ -102,22 +142,43 @@ impl Test for IoTest {
             self.get_valgrind_result(&project_definition, &options, &basedir, &vg_log_folder, &vg_filepath)?
         };
 
+        let passed = self.did_pass(&project_definition, &Evaluation {
+            exit_code_correct,
+            timeout: had_timeout,
+            limit_exceeded: limit_exceeded.is_some(),
+            distance,
+            stderr_distance,
+            add_distance,
+            mem_leaks,
+            mem_errors,
+        });
+        let similarity = similarity(&[Some(distance), add_diff.as_ref().map(|_| add_distance), stderr_distance]);
+        let (score, max_score) = self.get_score(&project_definition, passed, exit_code_correct, similarity, mem_leaks, mem_errors);
+
         Ok(Box::new(IoTestresult {
             diff: changeset,
             diff_distance: distance,
+            raw_output,
+            stderr_diff,
+            stderr_distance,
             add_distance: if add_diff.is_some() { Some(add_distance) } else { None },
             add_diff,
             add_file_missing,
             truncated_output,
             passed,
-            exit_code,
+            score,
+            max_score,
+            attempts: vec![],
+            exit_outcome,
             expected_exit_code: self.exp_exit_code,
+            expected_signal: self.exp_signal,
             mem_leaks,
             mem_errors,
             mem_logfile: format!("{}/{}/{}/vg_log.txt", &basedir, &vg_log_folder, self.meta.number),
             command_used: format!("{} {}", &project_definition.binary_path, &self.argv.clone().join(" ")),
             input,
             timeout: had_timeout,
+            limit_exceeded,
             name: self.meta.name.clone(),
             description: self.meta.description.clone().unwrap_or("".to_owned()),
             number: self.meta.number,
@@ -132,9 +193,7 @@ impl Test for IoTest {
 
 impl IoTest {
 
-    fn run_command_with_timeout(&self, command : &str, args: &Vec<String>, envs: &Vec<(String, String)>, timeout : u64) -> Result<(String, String, String, Option<i32>), TestingError> {
-        let project_definition = self.project_definition.upgrade().unwrap();
-
+    fn run_command_with_timeout(&self, command : &str, args: &Vec<String>, envs: &Vec<(String, String)>, workdir: &str, timeout : u64) -> Result<(String, String, String, String, ExitOutcome), TestingError> {
         let input: String;
         if !self.in_file.is_empty() {
             input = read_to_string(&self.in_file).map_err(|_| TestingError::InFileNotFound(self.in_file.clone()))?;
@@ -152,19 +211,20 @@ impl IoTest {
         }
 
         let mut cmd = subprocess::Exec::cmd(command)
-            .cwd(project_definition.makefile_path.as_ref().unwrap_or(&"./".to_owned()))
+            .cwd(workdir)
             .args(args)
             .args(&self.argv)
             .stdin(subprocess::Redirection::Pipe)
             .stdout(subprocess::Redirection::Pipe)
-            .stderr(subprocess::NullFile)
+            .stderr(subprocess::Redirection::Pipe)
             .env_extend(envs)
             .popen()
             .expect("Could not spawn process!");
 
 
-        let given_exit_code;
+        let given_exit_outcome;
         let given_output;
+        let given_stderr;
 
         let capture = cmd.communicate_start(Some(input.as_bytes().iter().cloned().collect()))
             .limit_time(std::time::Duration::new(timeout , 0))
@@ -172,39 +232,35 @@ impl IoTest {
 
         match capture {
             Ok(c) => {
-                given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
+                given_exit_outcome = wait_on_subprocess(&mut cmd, self.meta.number);
                 given_output = String::from_utf8_lossy(&c.0.unwrap_or(Vec::new())).into_owned();
+                given_stderr = String::from_utf8_lossy(&c.1.unwrap_or(Vec::new())).into_owned();
             }
 
             Err(e) => {
-                given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
+                given_exit_outcome = match wait_on_subprocess(&mut cmd, self.meta.number) {
+                    _ if e.kind() == std::io::ErrorKind::TimedOut => ExitOutcome::TimedOut,
+                    exit_outcome => exit_outcome,
+                };
                 given_output = String::from_utf8_lossy(&e.capture.0.unwrap_or(Vec::new())).into_owned();
+                given_stderr = String::from_utf8_lossy(&e.capture.1.unwrap_or(Vec::new())).into_owned();
             }
         }
 
-        let given_exit_code = match given_exit_code {
-            Some(v) => match v {
-                subprocess::ExitStatus::Exited(exit_code) => Some(exit_code as i32),
-                subprocess::ExitStatus::Other(exit_code) => Some(exit_code),
-                _ => None,
-            }
-            None => None,
-        };
-
-        return Ok((input, reference_output, given_output, given_exit_code));
+        return Ok((input, reference_output, given_output, given_stderr, given_exit_outcome));
     }
 }
 
-pub fn wait_on_subprocess(cmd: &mut subprocess::Popen, tc_number: i32) -> Option<subprocess::ExitStatus> {
+pub fn wait_on_subprocess(cmd: &mut subprocess::Popen, tc_number: i32) -> ExitOutcome {
     match cmd.wait_timeout(std::time::Duration::new(2, 0)).expect("Could not wait on process!") {
-        Some(exit_code) => Some(exit_code),
+        Some(exit_status) => ExitOutcome::from(exit_status),
         None => {
             eprintln!("Warning: testcase {} is still running, killing testcase!", tc_number);
             cmd.kill().expect("Could not kill testcase!");
             if cmd.wait_timeout(std::time::Duration::new(2, 0)).expect("Could not wait on process!").is_none() {
                 eprintln!("Warning: testcase {} is still running, failed to kill testcase! Moving on regardless...", tc_number);
             }
-            None
+            ExitOutcome::Killed
         }
     }
 }
@@ -228,7 +284,17 @@ pub fn prepare_valgrind(project_definition: &ProjectDefinition, options: &Testru
     (vg_log_folder, vg_filepath)
 }
 
-pub fn prepare_cmdline(project_definition: &ProjectDefinition, options: &TestrunnerOptions, vg_filepath: &str, unbuffer: bool) -> Result<(String, Vec<String>), TestingError> {
+pub fn prepare_sandbox(project_definition: &ProjectDefinition, options: &TestrunnerOptions, meta: &TestMeta, basedir: &str) -> Result<Option<Sandbox>, TestingError> {
+    if !meta.sandbox.or(project_definition.sandbox).unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let mut fixtures = project_definition.fixtures.clone().unwrap_or_default();
+    fixtures.extend(meta.fixtures.clone().unwrap_or_default());
+    Sandbox::create(basedir, &fixtures, meta.number, options.keep_sandboxes, options.sudo.is_some()).map(Some)
+}
+
+pub fn prepare_cmdline(project_definition: &ProjectDefinition, options: &TestrunnerOptions, limits: &ResourceLimits, sandbox: Option<&Sandbox>, vg_filepath: &str, status_filepath: &str, unbuffer: bool) -> Result<(String, Vec<String>), TestingError> {
     let mut args = Vec::<String>::new();
     if options.sudo.is_some() {
         check_program_availability("sudo")?;
@@ -236,6 +302,12 @@ pub fn prepare_cmdline(project_definition: &ProjectDefinition, options: &Testrun
         args.push("--preserve-env".to_owned());
         args.push(format!("--user={}", &options.sudo.as_ref().unwrap()));
     }
+    if options.isolation == Isolation::Namespaces {
+        args.push("unshare".to_owned());
+        args.extend(NAMESPACE_FLAGS.iter().map(|e| e.to_string()));
+    }
+    // the launcher runs the program (or valgrind) as its child, which is the only process affected by the limits
+    args.append(&mut launcher_cmdline(limits, status_filepath)?);
     if unbuffer {
         check_program_availability("stdbuf")?;
         args.push("stdbuf".to_owned());
@@ -252,15 +324,30 @@ pub fn prepare_cmdline(project_definition: &ProjectDefinition, options: &Testrun
             args.push("--show-leak-kinds=all".to_owned());
             args.push("--track-origins=yes".to_owned());
         }
-        args.push(format!("--log-file={}", &vg_filepath ));
+        // relative paths would be resolved against the sandbox
+        match sandbox {
+            Some(_) => args.push(format!("--log-file={}", absolute_path(".", vg_filepath))),
+            None => args.push(format!("--log-file={}", &vg_filepath)),
+        }
+    }
+    match sandbox {
+        Some(_) => {
+            let basedir = project_definition.makefile_path.clone().unwrap_or(".".to_owned());
+            args.push(absolute_path(&basedir, &project_definition.binary_path));
+        },
+        None => args.push(project_definition.binary_path.clone()),
     }
-    args.push(project_definition.binary_path.clone());
 
     let flags = args.split_off(1);
     let cmd_name = args.pop().unwrap();
     Ok((cmd_name, flags))
 }
 
+fn absolute_path(basedir: &str, path: &str) -> String {
+    let path = Path::new(basedir).join(path);
+    std::env::current_dir().map(|cwd| cwd.join(&path)).unwrap_or(path).to_string_lossy().into_owned()
+}
+
 pub fn prepare_envvars(env_vars: Option<&Vec<String>>) -> Vec<(String, String)> {
     match env_vars {
         Some(env_vec) => {
@@ -277,6 +364,25 @@ pub fn prepare_envvars(env_vars: Option<&Vec<String>>) -> Vec<(String, String)>
     }
 }
 
+pub fn read_reference_stderr(exp_stderr_file: Option<&String>, exp_stderr_string: Option<&String>) -> Result<Option<String>, TestingError> {
+    if let Some(path) = exp_stderr_file {
+        Ok(Some(read_to_string(path).map_err(|_| TestingError::RefFileNotFound(path.clone()))?))
+    }
+    else {
+        Ok(exp_stderr_string.cloned())
+    }
+}
+
+pub fn truncate_output(output: &mut String, ref_output_len: usize) -> bool {
+    if output.chars().count() > ref_output_len * 2 {
+        output.truncate(output.char_indices().nth(ref_output_len * 2).unwrap_or((512, ' ')).0);
+        true
+    }
+    else {
+        false
+    }
+}
+
 pub fn parse_vg_log(filepath: &String) -> Result<(i32, i32), TestingError> {
     let re = Regex::new(r"(?s)in use at exit: [0-9,]+ bytes? in (?P<leaks>[0-9,]+) blocks?.*ERROR SUMMARY: (?P<errors>[0-9,]+) errors? from [0-9,]+ contexts?")
         .unwrap();
@@ -298,6 +404,18 @@ pub fn parse_vg_log(filepath: &String) -> Result<(i32, i32), TestingError> {
     }
 }
 
+// `--kill-child` makes sure the program is killed along with `unshare` on timeouts
+const NAMESPACE_FLAGS: [&str; 8] = ["--user", "--map-root-user", "--mount", "--net", "--pid", "--fork", "--mount-proc", "--kill-child"];
+
+pub fn check_namespace_availability() -> Result<(), TestingError> {
+    check_program_availability("unshare")?;
+    match Command::new("unshare").args(&NAMESPACE_FLAGS).arg("true").stdin(Stdio::null()).output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(TestingError::IsolationUnavailable(String::from_utf8_lossy(&output.stderr).trim().to_owned())),
+        Err(err) => Err(TestingError::IsolationUnavailable(err.to_string())),
+    }
+}
+
 pub fn check_program_availability(prog: &str) -> Result<(), TestingError> {
     #[allow(unused_must_use)] // we don't care if child process was killed successfully
     match Command::new(prog)
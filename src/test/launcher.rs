@@ -0,0 +1,139 @@
+use std::fs::{read_to_string, remove_file, write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, exit};
+
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::test::{ExitOutcome, ResourceLimits, TestingError};
+
+
+/// First argument making the testrunner launch the tested program instead of running testcases, see `launch`.
+pub const LAUNCH_ARG: &str = "--launch-tested-program";
+
+/// Exit code if the tested program could not be launched, like the one of a shell.
+const EXIT_LAUNCH_FAILED: i32 = 127;
+
+/// Wait status of the tested program, passed from the launcher to the testrunner in a status file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LaunchStatus {
+    pub exit_code: Option<i32>,
+    pub signal: Option<u8>,
+    pub core_dumped: bool,
+    /// user and system CPU time of the program in seconds
+    pub cpu_time: f64,
+}
+
+impl LaunchStatus {
+    /// Reads and removes the status file, `None` if the launcher did not write it, e.g. as it was killed on a timeout.
+    pub fn read(path: &str) -> Option<Self> {
+        let status = read_to_string(path).ok().and_then(|status| serde_json::from_str(&status).ok());
+        let _ = remove_file(path);
+        status
+    }
+
+    pub fn exit_outcome(&self) -> ExitOutcome {
+        match self.signal {
+            Some(signal) => ExitOutcome::Signaled(signal, self.core_dumped),
+            None => ExitOutcome::Exited(self.exit_code.unwrap_or(0)),
+        }
+    }
+}
+
+/// Path for a new status file, which is written by the user running the program (see `--sudo`).
+pub fn status_filepath() -> String {
+    format!("{}/testrunner-status-{}", std::env::temp_dir().to_string_lossy(), Uuid::new_v4().to_simple())
+}
+
+/// Command line prefix running the rest of the command line through `launch`.
+pub fn launcher_cmdline(limits: &ResourceLimits, status_filepath: &str) -> Result<Vec<String>, TestingError> {
+    let exe = std::env::current_exe().map_err(|_| TestingError::MissingBinDependency("testrunner".to_owned()))?;
+    let mut args = vec![exe.to_string_lossy().into_owned(), LAUNCH_ARG.to_owned()];
+    args.push(format!("--status-file={}", status_filepath));
+    args.append(&mut limits.launcher_args());
+    args.push("--".to_owned());
+    Ok(args)
+}
+
+/// Runs the command following `--` as child process with the resource limits given in `args`,
+/// and writes its wait status to the status file.
+/// As only the tested program may be affected by the limits, they are set right before executing it.
+///
+/// Using a child process also keeps the program from running as PID 1 of a new PID namespace (see `--isolation`),
+/// for which the kernel ignores all signals without a handler, including those raised by the program itself.
+pub fn launch(args: &[String]) -> ! {
+    let mut limits = ResourceLimits::default();
+    let mut status_file = None;
+    let mut args = args.iter();
+    for arg in args.by_ref() {
+        if arg == "--" {
+            break;
+        }
+        let parsed = match arg.split_once('=') {
+            Some(("--status-file", value)) => {
+                status_file = Some(value.to_owned());
+                Ok(())
+            },
+            Some(("--memory", value)) => value.parse().map(|value| limits.memory = Some(value)),
+            Some(("--cpu", value)) => value.parse().map(|value| limits.cpu = Some(value)),
+            Some(("--fsize", value)) => value.parse().map(|value| limits.fsize = Some(value)),
+            Some(("--nproc", value)) => value.parse().map(|value| limits.nproc = Some(value)),
+            _ => launch_failed(&format!("unknown argument: {}", arg)),
+        };
+        if parsed.is_err() {
+            launch_failed(&format!("invalid limit: {}", arg));
+        }
+    }
+
+    let command = args.collect::<Vec<&String>>();
+    if command.is_empty() {
+        launch_failed("no command given");
+    }
+    let mut cmd = Command::new(command[0]);
+    cmd.args(&command[1..]);
+    // only async-signal-safe calls are allowed between fork and exec, which `prctl` and `setrlimit` are
+    unsafe {
+        cmd.pre_exec(move || {
+            // the testrunner kills the launcher on timeouts, which has to take the program along
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+            limits.apply()
+        });
+    }
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => launch_failed(&format!("could not execute {}: {}", command[0], err)),
+    };
+
+    let mut wait_status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    while unsafe { libc::wait4(child.id() as libc::pid_t, &mut wait_status, 0, &mut rusage) } == -1 {
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            launch_failed(&format!("could not wait for {}: {}", command[0], std::io::Error::last_os_error()));
+        }
+    }
+    let seconds = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1e6;
+    let cpu_time = seconds(rusage.ru_utime) + seconds(rusage.ru_stime);
+    let status = if libc::WIFSIGNALED(wait_status) {
+        LaunchStatus {
+            exit_code: None,
+            signal: Some(libc::WTERMSIG(wait_status) as u8),
+            core_dumped: libc::WCOREDUMP(wait_status),
+            cpu_time,
+        }
+    }
+    else {
+        LaunchStatus { exit_code: Some(libc::WEXITSTATUS(wait_status)), signal: None, core_dumped: false, cpu_time }
+    };
+    if let Some(path) = status_file {
+        if let Err(err) = write(&path, serde_json::to_string(&status).unwrap()) {
+            launch_failed(&format!("could not write status file {}: {}", path, err));
+        }
+    }
+    // like a shell, for callers not reading the status file
+    exit(status.exit_code.unwrap_or_else(|| 128 + status.signal.unwrap_or(0) as i32))
+}
+
+fn launch_failed(message: &str) -> ! {
+    eprintln!("testrunner: {}", message);
+    exit(EXIT_LAUNCH_FAILED)
+}
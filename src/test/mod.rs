@@ -1,5 +1,10 @@
 pub mod test;
 pub mod io_test;
 pub mod ordio_test;
+pub mod criteria;
 pub mod diff;
+pub mod launcher;
+pub mod normalize;
+pub mod sandbox;
+pub mod scoring;
 
@@ -0,0 +1,109 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::project::definition::ProjectDefinition;
+use super::test::TestMeta;
+
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Normalization {
+    #[serde(alias = "trim-trailing-ws")]
+    TrimTrailingWs,
+    #[serde(alias = "crlf")]
+    Crlf,
+    #[serde(alias = "collapse-blank-lines")]
+    CollapseBlankLines,
+    #[serde(alias = "case-insensitive")]
+    CaseInsensitive,
+    #[serde(alias = "final-newline")]
+    FinalNewline,
+}
+
+/// The normalizations of the testcase, falling back to those of the project.
+pub fn get_normalizations(project_definition: &ProjectDefinition, meta: &TestMeta) -> Vec<Normalization> {
+    meta.normalize.clone().or(project_definition.normalize.clone()).unwrap_or_default()
+}
+
+/// Applies the given normalizations to `text`, always in the same order, regardless of their order in the config.
+pub fn normalize(text: &str, normalizations: &[Normalization]) -> String {
+    let enabled = |normalization| normalizations.contains(&normalization);
+    let mut text = text.to_owned();
+
+    if enabled(Normalization::Crlf) {
+        text = text.replace("\r\n", "\n");
+    }
+    if enabled(Normalization::TrimTrailingWs) {
+        text = text.split_inclusive('\n').map(|line| {
+            match line.strip_suffix('\n') {
+                Some(line) => format!("{}\n", line.trim_end()),
+                None => line.trim_end().to_owned(),
+            }
+        }).collect();
+    }
+    if enabled(Normalization::CollapseBlankLines) {
+        let mut prev_blank = false;
+        text = text.split_inclusive('\n').filter(|line| {
+            let blank = line.trim().is_empty();
+            let keep = !(blank && prev_blank);
+            prev_blank = blank;
+            keep
+        }).collect();
+    }
+    if enabled(Normalization::FinalNewline) && !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    if enabled(Normalization::CaseInsensitive) {
+        text = text.to_lowercase();
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Normalization::*;
+
+    #[test]
+    fn no_normalizations_keep_text() {
+        assert_eq!(normalize("a \r\n\n\nB", &[]), "a \r\n\n\nB");
+    }
+
+    #[test]
+    fn crlf_becomes_lf() {
+        assert_eq!(normalize("a\r\nb\r\n", &[Crlf]), "a\nb\n");
+        assert_eq!(normalize("a\rb", &[Crlf]), "a\rb");
+    }
+
+    #[test]
+    fn trailing_whitespace_is_trimmed_per_line() {
+        assert_eq!(normalize("a \t\n b  \nc ", &[TrimTrailingWs]), "a\n b\nc");
+        // without crlf, the carriage return counts as trailing whitespace
+        assert_eq!(normalize("a \r\n", &[TrimTrailingWs]), "a\n");
+    }
+
+    #[test]
+    fn blank_lines_are_collapsed() {
+        assert_eq!(normalize("a\n\n\n\nb\n\n", &[CollapseBlankLines]), "a\n\nb\n\n");
+        assert_eq!(normalize("a\n  \n\t\nb", &[CollapseBlankLines]), "a\n  \nb");
+    }
+
+    #[test]
+    fn final_newline_is_added_once() {
+        assert_eq!(normalize("a", &[FinalNewline]), "a\n");
+        assert_eq!(normalize("a\n", &[FinalNewline]), "a\n");
+        assert_eq!(normalize("", &[FinalNewline]), "");
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(normalize("Hello WORLD", &[CaseInsensitive]), "hello world");
+    }
+
+    #[test]
+    fn order_of_normalizations_does_not_matter() {
+        let text = "A \r\n\r\n \r\nB";
+        let expected = "a\n\nb\n";
+        assert_eq!(normalize(text, &[Crlf, TrimTrailingWs, CollapseBlankLines, FinalNewline, CaseInsensitive]), expected);
+        assert_eq!(normalize(text, &[CaseInsensitive, FinalNewline, CollapseBlankLines, TrimTrailingWs, Crlf]), expected);
+    }
+}
@@ -1,12 +1,15 @@
 use std::clone::Clone;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::sync::Weak;
+use std::thread;
 use std::time::{Duration, Instant};
 
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use regex::Regex;
 use serde::{Deserializer, Deserialize};
 use serde_derive::Serialize;
+use similar::{Algorithm, DiffOp, capture_diff_slices};
 
 use crate::project::binary::Binary;
 use crate::project::definition::ProjectDefinition;
@@ -15,14 +18,19 @@ use crate::testresult::ordio_testresult::OrdIoTestresult;
 use crate::testresult::testresult::Testresult;
 use crate::testrunner::{TestrunnerError, TestrunnerOptions};
 use super::diff::ChangesetInline;
-use super::io_test::{prepare_cmdline, prepare_envvars, prepare_valgrind, wait_on_subprocess};
-use super::test::{Test, TestMeta, TestcaseType, TestingError};
+use super::criteria::Evaluation;
+use super::scoring::similarity;
+use super::normalize::{Normalization, get_normalizations, normalize};
+use super::launcher::{LaunchStatus, status_filepath};
+use super::io_test::{prepare_cmdline, prepare_envvars, prepare_sandbox, prepare_valgrind, read_reference_stderr, truncate_output, wait_on_subprocess};
+use super::test::{ExitOutcome, ResourceLimits, Test, TestMeta, TestcaseType, TestingError, deserialize_signal};
 
 
 #[derive(Clone, Debug)]
 pub enum InputOutput {
     Input(String),
     Output(String),
+    ErrOutput(String),
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -30,6 +38,13 @@ pub enum IODiff {
     Input(String),
     InputUnsent(String),
     Output(Vec<ChangesetInline<String>>),
+    ErrOutput(Vec<ChangesetInline<String>>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stream {
+    Stdout,
+    Stderr,
 }
 
 impl InputOutput {
@@ -37,6 +52,7 @@ impl InputOutput {
         match self {
             InputOutput::Input(_) => true,
             InputOutput::Output(_) => false,
+            InputOutput::ErrOutput(_) => false,
         }
     }
 
@@ -44,6 +60,15 @@ impl InputOutput {
         match self {
             InputOutput::Input(_) => false,
             InputOutput::Output(_) => true,
+            InputOutput::ErrOutput(_) => false,
+        }
+    }
+
+    fn is_err_output(&self) -> bool {
+        match self {
+            InputOutput::Input(_) => false,
+            InputOutput::Output(_) => false,
+            InputOutput::ErrOutput(_) => true,
         }
     }
 
@@ -51,8 +76,116 @@ impl InputOutput {
         match self {
             InputOutput::Input(s) => &s,
             InputOutput::Output(s) => &s,
+            InputOutput::ErrOutput(s) => &s,
+        }
+    }
+
+    fn to_iodiff(&self, changeset: Vec<ChangesetInline<String>>) -> IODiff {
+        match self {
+            InputOutput::ErrOutput(_) => IODiff::ErrOutput(changeset),
+            _ => IODiff::Output(changeset),
+        }
+    }
+}
+
+/// Captures stdout and stderr of a running program in the order their chunks arrived.
+struct PipeCapture {
+    receiver: Receiver<(Instant, Stream, Vec<u8>)>,
+    chunks: Vec<(Instant, Stream, Vec<u8>)>,
+    stderr: Vec<u8>,
+    disconnected: bool,
+}
+
+impl PipeCapture {
+    fn start(stdout: File, stderr: File) -> Self {
+        let (sender, receiver) = unbounded();
+        PipeCapture::spawn_reader(stdout, Stream::Stdout, sender.clone());
+        PipeCapture::spawn_reader(stderr, Stream::Stderr, sender);
+        PipeCapture { receiver, chunks: Vec::new(), stderr: Vec::new(), disconnected: false }
+    }
+
+    fn spawn_reader(mut pipe: File, stream: Stream, sender: Sender<(Instant, Stream, Vec<u8>)>) {
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => {
+                        if sender.send((Instant::now(), stream, buf[..len].to_vec())).is_err() {
+                            break;
+                        }
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+
+    // receive output until `deadline`, or until both pipes have been closed
+    fn receive_until(&mut self, deadline: Instant) {
+        if self.disconnected {
+            return;
+        }
+        loop {
+            match self.receiver.recv_deadline(deadline) {
+                Ok(chunk) => {
+                    if chunk.1 == Stream::Stderr {
+                        self.stderr.extend_from_slice(&chunk.2);
+                    }
+                    self.chunks.push(chunk);
+                },
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.disconnected = true;
+                    break;
+                },
+            }
+        }
+    }
+
+    // stdout received since the last flush
+    fn stdout(&self) -> String {
+        let stdout = self.chunks.iter()
+            .filter(|(_, stream, _)| *stream == Stream::Stdout)
+            .flat_map(|(_, _, data)| data.iter().cloned())
+            .collect::<Vec<u8>>();
+        String::from_utf8_lossy(&stdout).into_owned()
+    }
+
+    // move received chunks into `io`, merging them with the last entry where possible
+    fn flush(&mut self, io: &mut Vec<InputOutput>, interleave_stderr: bool) {
+        let mut chunks = std::mem::take(&mut self.chunks);
+        chunks.sort_by_key(|(time, _, _)| *time);
+
+        let mut outputs: Vec<(Stream, Vec<u8>)> = Vec::new();
+        for (_, stream, data) in chunks {
+            if stream == Stream::Stderr && !interleave_stderr {
+                continue;
+            }
+            match outputs.last_mut() {
+                Some((prev_stream, prev_data)) if *prev_stream == stream => prev_data.extend(data),
+                _ => outputs.push((stream, data)),
+            }
+        }
+        if outputs.is_empty() {
+            outputs.push((Stream::Stdout, Vec::new()));
+        }
+
+        for (stream, data) in outputs {
+            let data = String::from_utf8_lossy(&data).into_owned();
+            match (io.last_mut(), stream) {
+                (Some(InputOutput::Output(prev_e)), Stream::Stdout) => prev_e.push_str(&data),
+                (Some(InputOutput::ErrOutput(prev_e)), Stream::Stderr) => prev_e.push_str(&data),
+                (_, Stream::Stdout) => io.push(InputOutput::Output(data)),
+                (_, Stream::Stderr) => io.push(InputOutput::ErrOutput(data)),
+            }
         }
     }
+
+    fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -70,9 +203,13 @@ pub struct OrdIoTest {
     #[serde(skip_serializing, deserialize_with = "OrdIoTest::deserialize_regex")]
     io_prompt: Regex,
     io_file: String,
+    exp_stderr_file: Option<String>,
+    exp_stderr_string: Option<String>,
     #[serde(default)]
     argv: Vec<String>,
     exp_exit_code: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_signal")]
+    exp_signal: Option<u8>,
     env_vars: Option<Vec<String>>,
 }
 
@@ -107,14 +244,24 @@ impl Test for OrdIoTest {
 
         let basedir = project_definition.makefile_path.clone().unwrap_or(".".to_owned());
         let (vg_log_folder, vg_filepath) = prepare_valgrind(&project_definition, &options, &self.meta, &basedir);
-        let (cmd_name, flags) = prepare_cmdline(&project_definition, &options, &vg_filepath, true)?;
+        let sandbox = prepare_sandbox(&project_definition, &options, &self.meta, &basedir)?;
+        let workdir = sandbox.as_ref().map(|s| s.path().to_string_lossy().into_owned()).unwrap_or(basedir.clone());
+        let limits = ResourceLimits::new(&project_definition, &self.meta);
+        let status_filepath = status_filepath();
+        let (cmd_name, flags) = prepare_cmdline(&project_definition, &options, &limits, sandbox.as_ref(), &vg_filepath, &status_filepath, true)?;
         let env_vars = prepare_envvars(self.env_vars.as_ref());
 
         let global_timeout = project_definition.global_timeout.unwrap_or(5);
         let timeout = self.meta.timeout.unwrap_or(global_timeout);
 
-        let (mut io, exit_code) = self.run_command_with_timeout(&cmd_name, &flags, &env_vars, timeout)?;
-        let had_timeout = !exit_code.is_some();
+        let mut reference_stderr = read_reference_stderr(self.exp_stderr_file.as_ref(), self.exp_stderr_string.as_ref())?;
+        let (mut io, mut given_stderr, exit_outcome) = self.run_command_with_timeout(&cmd_name, &flags, &env_vars, &workdir, timeout)?;
+        // the launcher reports the wait status of the program itself, unless it was killed on a timeout
+        let launch_status = LaunchStatus::read(&status_filepath).filter(|_| !exit_outcome.had_timeout());
+        let exit_outcome = launch_status.as_ref().map_or(exit_outcome, |status| status.exit_outcome());
+        let had_timeout = exit_outcome.had_timeout();
+        let exit_code_correct = exit_outcome.matches(self.exp_exit_code, self.exp_signal) && !had_timeout;
+        let limit_exceeded = limits.exceeded(exit_outcome, exit_code_correct, launch_status.map(|status| status.cpu_time));
         let mut truncated_output = false;
         if had_timeout {
             let ref_output_len = match self.io.iter().rfind(|io_e| io_e.is_output()) {
@@ -126,21 +273,33 @@ impl Test for OrdIoTest {
             if let Some(io_e) = io.iter_mut().rfind(|io_e| io_e.is_output()) {
                 match io_e {
                     InputOutput::Output(ref mut out) => {
-                        if out.chars().count() > ref_output_len * 2 {
-                            out.truncate(out.char_indices().nth(ref_output_len * 2).unwrap_or((512, ' ')).0);
-                            truncated_output = true;
-                        }
+                        truncated_output |= truncate_output(out, ref_output_len);
                     },
                     _ => {},
                 }
             }
+            if let Some(ref reference_stderr) = reference_stderr {
+                truncated_output |= truncate_output(&mut given_stderr, reference_stderr.chars().count());
+            }
         }
 
-        let (io_diff, distance) = self.calculate_diff(io, timeout)?;
+        let normalizations = get_normalizations(&project_definition, &self.meta);
+        let raw_output = if normalizations.is_empty() { None } else { Some(io.iter().map(|e| e.get_ref().as_str()).collect::<String>()) };
+        if !normalizations.is_empty() {
+            reference_stderr = reference_stderr.map(|e| normalize(&e, &normalizations));
+            given_stderr = normalize(&given_stderr, &normalizations);
+        }
 
-        let (add_diff, add_distance, add_file_missing) = self.get_add_diff()?;
+        let (io_diff, distance) = self.calculate_diff(io, &normalizations, timeout)?;
+        let (stderr_diff, stderr_distance) = match reference_stderr {
+            Some(ref reference_stderr) => {
+                let (changeset, distance) = diff_plaintext(reference_stderr, &given_stderr, Duration::from_secs(timeout));
+                (Some(changeset), Some(distance))
+            },
+            None => (None, None),
+        };
 
-        let passed = self.did_pass(self.exp_exit_code, exit_code, distance, add_distance, had_timeout);
+        let (add_diff, add_distance, add_file_missing) = self.get_add_diff(sandbox.as_ref())?;
 
         let input = self.io.iter().map(|e| {
             match e {
@@ -156,22 +315,43 @@ impl Test for OrdIoTest {
             self.get_valgrind_result(&project_definition, &options, &basedir, &vg_log_folder, &vg_filepath)?
         };
 
+        let passed = self.did_pass(&project_definition, &Evaluation {
+            exit_code_correct,
+            timeout: had_timeout,
+            limit_exceeded: limit_exceeded.is_some(),
+            distance,
+            stderr_distance,
+            add_distance,
+            mem_leaks,
+            mem_errors,
+        });
+        let similarity = similarity(&[Some(distance), add_diff.as_ref().map(|_| add_distance), stderr_distance]);
+        let (score, max_score) = self.get_score(&project_definition, passed, exit_code_correct, similarity, mem_leaks, mem_errors);
+
         Ok(Box::new(OrdIoTestresult {
             io_diff,
             diff_distance: distance,
+            raw_output,
+            stderr_diff,
+            stderr_distance,
             add_distance: if add_diff.is_some() { Some(add_distance) } else { None },
             add_diff,
             add_file_missing,
             truncated_output,
             passed,
-            exit_code,
+            score,
+            max_score,
+            attempts: vec![],
+            exit_outcome,
             expected_exit_code: self.exp_exit_code,
+            expected_signal: self.exp_signal,
             mem_leaks,
             mem_errors,
             mem_logfile: format!("{}/{}/{}/vg_log.txt", &basedir, &vg_log_folder, self.meta.number),
             command_used: format!("{} {}", &project_definition.binary_path, &self.argv.clone().join(" ")),
             input,
             timeout: had_timeout,
+            limit_exceeded,
             name: self.meta.name.clone(),
             description: self.meta.description.clone().unwrap_or("".to_owned()),
             number: self.meta.number,
@@ -186,59 +366,106 @@ impl Test for OrdIoTest {
 
 impl OrdIoTest {
 
-    fn calculate_diff(&self, io: Vec<InputOutput>, timeout: u64) -> Result<(Vec<IODiff>, f32), TestingError> {
+    fn calculate_diff(&self, io: Vec<InputOutput>, normalizations: &[Normalization], timeout: u64) -> Result<(Vec<IODiff>, f32), TestingError> {
+        let timeout = Duration::from_secs(timeout);
+        let ref_io = OrdIoTest::normalize_io(&self.io, normalizations);
+        let io = OrdIoTest::normalize_io(&io, normalizations);
         let mut len_ref_sum = 0;
         let mut distances = Vec::with_capacity(io.len() / 2 + 2);
-        let mut io_mismatch = false;
-        let mut it_ref_io = self.io.iter();
-        let mut it_io = io.iter();
         let mut io_diff = Vec::<IODiff>::with_capacity(self.io.len());
-        while let Some(ref_io_e) = it_ref_io.next() {
-            let io_e = it_io.next();
-            if io_e.is_some() && !((ref_io_e.is_input() && io_e.unwrap().is_input()) || (ref_io_e.is_output() && io_e.unwrap().is_output())) {
-                io_mismatch = true;
-            }
 
-            let diff_e = match io_e {
-                Some(io_e) => {
-                    match ref_io_e {
-                        InputOutput::Input(input) => IODiff::Input(input.to_string()),
-                        InputOutput::Output(output) => {
-                            len_ref_sum += output.len();
-                            let (changeset, distance) = diff_plaintext(output, io_e.get_ref(), Duration::from_secs(timeout));
-                            distances.push(distance * output.len() as f32);
-                            IODiff::Output(changeset)
-                        },
-                    }
-                },
-                None => {
-                    match ref_io_e {
-                        InputOutput::Input(input) => IODiff::InputUnsent(input.to_string()),
-                        InputOutput::Output(output) => {
-                            len_ref_sum += output.len();
-                            let (changeset, distance) = diff_plaintext(output, "", Duration::from_secs(timeout));
-                            distances.push(distance * output.len() as f32);
-                            IODiff::Output(changeset)
-                        },
-                    }
-                },
-            };
-            io_diff.push(diff_e);
-        }
-        if let Some(io_e) = it_io.next() {
-            if let InputOutput::Output(output) = io_e {
-                len_ref_sum += output.len();
-                let (changeset, _) = diff_plaintext("", output, Duration::from_secs(timeout));
-                io_diff.push(IODiff::Output(changeset));
+        let ref_segments = OrdIoTest::split_segments(&ref_io);
+        let segments = OrdIoTest::split_segments(&io);
+        let mut it_segments = segments.iter();
+        for (ref_outputs, ref_input) in ref_segments.iter() {
+            let segment = it_segments.next();
+            let outputs = segment.map(|(outputs, _)| outputs.as_slice()).unwrap_or(&[]);
+            OrdIoTest::diff_segment(ref_outputs, outputs, timeout, &mut io_diff, &mut len_ref_sum, &mut distances);
+
+            let input = segment.and_then(|(_, input)| *input);
+            match ref_input {
+                Some(ref_input) if input.is_some() => io_diff.push(IODiff::Input(ref_input.to_string())),
+                Some(ref_input) => io_diff.push(IODiff::InputUnsent(ref_input.to_string())),
+                None if input.is_some() => return Err(TestingError::IOMismatch),
+                None => {},
             }
         }
-        if io_mismatch {
-            return Err(TestingError::IOMismatch);
+        for (outputs, input) in it_segments {
+            if input.is_some() {
+                return Err(TestingError::IOMismatch);
+            }
+            OrdIoTest::diff_segment(&[], outputs, timeout, &mut io_diff, &mut len_ref_sum, &mut distances);
         }
+
         let distance = distances.iter().sum::<f32>() / len_ref_sum as f32;
         Ok((io_diff, distance))
     }
 
+    // normalize outputs chunk-wise, only the last output gets a final newline
+    fn normalize_io(io: &[InputOutput], normalizations: &[Normalization]) -> Vec<InputOutput> {
+        let chunk_normalizations = normalizations.iter().cloned()
+            .filter(|e| *e != Normalization::FinalNewline)
+            .collect::<Vec<Normalization>>();
+        let last_output = io.iter().rposition(|e| !e.is_input());
+        io.iter().enumerate().map(|(i, io_e)| {
+            let normalizations = if Some(i) == last_output { normalizations } else { &chunk_normalizations };
+            match io_e {
+                InputOutput::Input(input) => InputOutput::Input(input.clone()),
+                InputOutput::Output(output) => InputOutput::Output(normalize(output, normalizations)),
+                InputOutput::ErrOutput(output) => InputOutput::ErrOutput(normalize(output, normalizations)),
+            }
+        }).collect()
+    }
+
+    // split into the outputs read before each input, and the input itself
+    fn split_segments(io: &[InputOutput]) -> Vec<(Vec<&InputOutput>, Option<&String>)> {
+        let mut segments = Vec::with_capacity(io.len() / 2 + 1);
+        let mut outputs = Vec::new();
+        for io_e in io.iter() {
+            match io_e {
+                InputOutput::Input(input) => segments.push((std::mem::take(&mut outputs), Some(input))),
+                _ => outputs.push(io_e),
+            }
+        }
+        if !outputs.is_empty() {
+            segments.push((outputs, None));
+        }
+        segments
+    }
+
+    fn diff_segment(ref_outputs: &[&InputOutput], outputs: &[&InputOutput], timeout: Duration, io_diff: &mut Vec<IODiff>, len_ref_sum: &mut usize, distances: &mut Vec<f32>) {
+        // align outputs by stream first, a chunk written to the wrong stream or in the wrong order counts as missing
+        let ref_streams = ref_outputs.iter().map(|io_e| io_e.is_err_output()).collect::<Vec<bool>>();
+        let streams = outputs.iter().map(|io_e| io_e.is_err_output()).collect::<Vec<bool>>();
+
+        for op in capture_diff_slices(Algorithm::Myers, &ref_streams, &streams) {
+            if let DiffOp::Equal { .. } = op {
+                for (ref_io_e, io_e) in ref_outputs[op.old_range()].iter().zip(outputs[op.new_range()].iter()) {
+                    let output = ref_io_e.get_ref();
+                    *len_ref_sum += output.len();
+                    let (changeset, distance) = diff_plaintext(output, io_e.get_ref(), timeout);
+                    distances.push(distance * output.len() as f32);
+                    io_diff.push(ref_io_e.to_iodiff(changeset));
+                }
+            }
+            else {
+                for ref_io_e in ref_outputs[op.old_range()].iter() {
+                    let output = ref_io_e.get_ref();
+                    *len_ref_sum += output.len();
+                    let (changeset, distance) = diff_plaintext(output, "", timeout);
+                    distances.push(distance * output.len() as f32);
+                    io_diff.push(ref_io_e.to_iodiff(changeset));
+                }
+                for io_e in outputs[op.new_range()].iter() {
+                    let output = io_e.get_ref();
+                    *len_ref_sum += output.len();
+                    let (changeset, _) = diff_plaintext("", output, timeout);
+                    io_diff.push(io_e.to_iodiff(changeset));
+                }
+            }
+        }
+    }
+
     fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
         where D: Deserializer<'de>
     {
@@ -259,6 +486,9 @@ impl OrdIoTest {
                 else if e.starts_with("? ") {
                     curr_io = InputOutput::Output(format!("{}", e.strip_prefix("? ").unwrap()));
                 }
+                else if e.starts_with("! ") {
+                    curr_io = InputOutput::ErrOutput(format!("{}\n", e.strip_prefix("! ").unwrap()));
+                }
                 else if e.starts_with("< ") {
                     curr_io = InputOutput::Input(format!("{}\n", e.strip_prefix("< ").unwrap()));
                 }
@@ -280,6 +510,14 @@ impl OrdIoTest {
                                 acc.push(curr_io);
                             }
                         },
+                        InputOutput::ErrOutput(ref curr_e) => {
+                            if let InputOutput::ErrOutput(prev_e) = prev_io {
+                                prev_e.push_str(&curr_e);
+                            }
+                            else {
+                                acc.push(curr_io);
+                            }
+                        },
                         InputOutput::Input(ref curr_e) => {
                             if let InputOutput::Input(prev_e) = prev_io {
                                 prev_e.push_str(&curr_e);
@@ -301,42 +539,33 @@ impl OrdIoTest {
         }))
     }
 
-    fn run_command_with_timeout(&self, command: &str, args: &Vec<String>, envs: &Vec<(String, String)>, timeout: u64)-> Result<(Vec<InputOutput>, Option<i32>), TestingError> {
-        let project_definition = self.project_definition.upgrade().unwrap();
-
+    fn run_command_with_timeout(&self, command: &str, args: &Vec<String>, envs: &Vec<(String, String)>, workdir: &str, timeout: u64)-> Result<(Vec<InputOutput>, String, ExitOutcome), TestingError> {
         let timeout = Duration::from_secs(timeout);
+        let interleave_stderr = self.io.iter().any(|e| e.is_err_output());
         let mut has_finished = false;
-        let mut ref_io = self.io.iter();
+        let mut timed_out = false;
+        let mut ref_io = self.io.iter().peekable();
         let mut io: Vec<InputOutput> = Vec::with_capacity(self.io.len());
 
         let mut cmd = subprocess::Exec::cmd(command)
-            .cwd(project_definition.makefile_path.as_ref().unwrap_or(&"./".to_owned()))
+            .cwd(workdir)
             .args(args)
             .args(&self.argv)
             .stdin(subprocess::Redirection::Pipe)
             .stdout(subprocess::Redirection::Pipe)
-            .stderr(subprocess::NullFile)
+            .stderr(subprocess::Redirection::Pipe)
             .env_extend(envs)
             .popen()
             .expect("Could not spawn process!");
 
-        let mut stdin = cmd.stdin.as_ref().unwrap().try_clone().unwrap();
+        let mut stdin = cmd.stdin.take().unwrap();
+        let mut capture = PipeCapture::start(cmd.stdout.take().unwrap(), cmd.stderr.take().unwrap());
         let mut curr_io = ref_io.next().unwrap().clone();
 
-        let mut communicator = cmd.communicate_start(Some("".as_bytes().iter().cloned().collect()))
-            .limit_time(Duration::from_millis(250));
-
         // check for some initial unexpected output
         if curr_io.get_ref().is_empty() {
-            let result = communicator.read();
-            match result {
-                Ok(comm) => {
-                    io.push(InputOutput::Output(String::from_utf8_lossy(&comm.0.unwrap_or(vec![])).into_owned()));
-                },
-                Err(err) => {
-                    io.push(InputOutput::Output(String::from_utf8_lossy(&err.capture.0.unwrap_or(vec![])).into_owned()));
-                }
-            }
+            capture.receive_until(Instant::now() + Duration::from_millis(250));
+            capture.flush(&mut io, interleave_stderr);
             curr_io = ref_io.next().unwrap().clone();
         }
 
@@ -358,60 +587,44 @@ impl OrdIoTest {
                         }
                     }
                 },
-                InputOutput::Output(_) => {
-                    let mut output = String::with_capacity(self.io.iter().filter(|e| e.is_output()).fold(0, |acc, e| acc + e.get_ref().len()));
+                _ => {
+                    // output and error output up to the next input are read together
+                    while ref_io.peek().map_or(false, |e| !e.is_input()) {
+                        ref_io.next();
+                    }
+
                     loop {
-                        let result = communicator.read();
-                        match result {
-                            Ok(comm) => {
-                                output.push_str(&String::from_utf8_lossy(&comm.0.unwrap_or(vec![])));
-                            },
-                            Err(err) => {
-                                output.push_str(&String::from_utf8_lossy(&err.capture.0.clone().unwrap_or(vec![])));
-                                if err.kind() != io::ErrorKind::TimedOut {
-                                    break;
-                                }
-                            }
+                        capture.receive_until(Instant::now() + Duration::from_millis(250));
+                        if capture.disconnected {
+                            // output is closed, so just wait for the program to exit
+                            let _ = cmd.wait_timeout(Duration::from_millis(250));
                         }
 
                         let currtime = Instant::now();
                         if currtime - starttime > timeout {
-                            let given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
-                            if exit_code.is_none() {
-                                exit_code = given_exit_code;
-                            }
+                            exit_code = Some(wait_on_subprocess(&mut cmd, self.meta.number));
+                            timed_out = true;
 
-                            io.push(InputOutput::Output(output));
+                            capture.flush(&mut io, interleave_stderr);
                             has_finished = true;
                             break 'io_loop;
                         }
 
-                        exit_code = cmd.poll();
+                        exit_code = cmd.poll().map(ExitOutcome::from);
                         if exit_code.is_some() {
                             // check for some final output
-                            let result = communicator.read();
-                            match result {
-                                Ok(comm) => {
-                                    output.push_str(&String::from_utf8_lossy(&comm.0.unwrap_or(vec![])));
-                                },
-                                Err(err) => {
-                                    output.push_str(&String::from_utf8_lossy(&err.capture.0.clone().unwrap_or(vec![])));
-                                    if err.kind() != io::ErrorKind::TimedOut {
-                                        break;
-                                    }
-                                }
-                            }
+                            capture.receive_until(Instant::now() + Duration::from_millis(250));
 
-                            io.push(InputOutput::Output(output));
+                            capture.flush(&mut io, interleave_stderr);
                             has_finished = true;
                             break 'io_loop;
                         }
 
-                        if self.io_prompt.is_match(&output) {
+                        if self.io_prompt.is_match(&capture.stdout()) {
                             break;
                         }
                     }
-                    io.push(InputOutput::Output(output));
+                    capture.flush(&mut io, interleave_stderr);
                 }
             }
             if curr_io.is_input() {
@@ -426,56 +639,30 @@ impl OrdIoTest {
 
         let currtime = Instant::now();
         if currtime - starttime > timeout {
-            exit_code = None;
+            timed_out = true;
         }
 
         // check for some final output
         if !has_finished {
-            let given_exit_code;
-            let given_output;
-
             drop(stdin);
-            communicator = communicator.limit_time(std::cmp::max(timeout - (Instant::now() - starttime), Duration::from_millis(250)));
-            let capture = communicator.read();
-
-            match capture {
-                Ok(c) => {
-                    given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
-                    given_output = String::from_utf8_lossy(&c.0.unwrap_or(Vec::new())).into_owned();
-                }
-
-                Err(e) => {
-                    given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
-                    given_output = String::from_utf8_lossy(&e.capture.0.unwrap_or(Vec::new())).into_owned();
-                }
-            }
+            let remaining = timeout.checked_sub(Instant::now() - starttime).unwrap_or_default();
+            capture.receive_until(Instant::now() + std::cmp::max(remaining, Duration::from_millis(250)));
+            let given_exit_code = wait_on_subprocess(&mut cmd, self.meta.number);
 
             if exit_code.is_none() {
-                exit_code = given_exit_code;
+                exit_code = Some(given_exit_code);
             }
 
-            if let Some(prev_io) = io.last_mut() {
-                match prev_io {
-                    InputOutput::Output(ref mut prev_e) => {
-                        prev_e.push_str(&given_output)
-                    },
-                    _ => {
-                        io.push(InputOutput::Output(given_output));
-                    }
-                }
-            }
+            capture.flush(&mut io, interleave_stderr);
         }
 
-        let exit_code = match exit_code {
-            Some(v) => match v {
-                subprocess::ExitStatus::Exited(exit_code) => Some(exit_code as i32),
-                subprocess::ExitStatus::Other(exit_code) => Some(exit_code),
-                _ => None,
-            },
-            None => None,
+        let exit_outcome = if timed_out {
+            ExitOutcome::TimedOut
+        }
+        else {
+            exit_code.unwrap_or(ExitOutcome::Killed)
         };
 
-        Ok((io, exit_code))
+        Ok((io, capture.stderr(), exit_outcome))
     }
 }
-
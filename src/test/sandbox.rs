@@ -0,0 +1,87 @@
+use std::fs::{copy, create_dir_all, read_dir, remove_dir_all};
+#[cfg(unix)]
+use std::fs::{Permissions, set_permissions};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use uuid::Uuid;
+
+use super::test::TestingError;
+
+
+/// A scratch working directory for a single testcase, removed again when dropped.
+pub struct Sandbox {
+    path: PathBuf,
+    number: i32,
+    keep: bool,
+}
+
+impl Sandbox {
+    /// Creates a fresh temporary directory and copies the fixtures into it.
+    /// Fixtures are given relative to `basedir`, and keep their relative path inside the sandbox.
+    pub fn create(basedir: &str, fixtures: &[String], number: i32, keep: bool, shared: bool) -> Result<Sandbox, TestingError> {
+        let path = std::env::temp_dir().join(format!("testrunner-sandbox-{}-{}", number, Uuid::new_v4().to_simple()));
+        create_dir_all(&path)?;
+        let sandbox = Sandbox { path, number, keep };
+
+        for fixture in fixtures {
+            let source = Path::new(basedir).join(fixture);
+            if !source.exists() {
+                return Err(TestingError::FixtureNotFound(fixture.clone()));
+            }
+            // fixtures outside of `basedir` are placed directly in the sandbox
+            let target = match Path::new(fixture) {
+                f if f.is_absolute() || f.components().any(|c| c == Component::ParentDir) => {
+                    sandbox.path.join(f.file_name().unwrap_or_default())
+                },
+                f => sandbox.path.join(f),
+            };
+            copy_recursive(&source, &target)?;
+        }
+
+        // the program runs as another user when using sudo
+        #[cfg(unix)]
+        if shared {
+            set_permissions(&sandbox.path, Permissions::from_mode(0o777))?;
+        }
+
+        Ok(sandbox)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn resolve(&self, file: &str) -> String {
+        self.path.join(file).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if self.keep {
+            eprintln!("Info: keeping sandbox of testcase {}: {}", self.number, self.path.display());
+        }
+        else {
+            remove_dir_all(&self.path).unwrap_or(());
+        }
+    }
+}
+
+fn copy_recursive(source: &Path, target: &Path) -> Result<(), TestingError> {
+    if source.is_dir() {
+        create_dir_all(target)?;
+        for entry in read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    }
+    else {
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        copy(source, target)?;
+    }
+    Ok(())
+}
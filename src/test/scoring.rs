@@ -0,0 +1,68 @@
+use serde_derive::Deserialize;
+
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum ScoringMode {
+    /// full points if the testcase passed, none otherwise
+    #[serde(alias = "binary")]
+    Binary,
+    /// points are split between output similarity, exit code and valgrind results
+    #[serde(alias = "partial")]
+    Partial,
+}
+
+impl Default for ScoringMode {
+    fn default() -> ScoringMode {
+        ScoringMode::Binary
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Scoring {
+    #[serde(default)]
+    pub mode: ScoringMode,
+    pub output: Option<f32>,
+    pub exit_code: Option<f32>,
+    pub valgrind: Option<f32>,
+}
+
+impl Scoring {
+    /// `valgrind_clean` is `None` if no valgrind results are available, its share is ignored in this case.
+    pub fn score(&self, max_score: f32, passed: bool, similarity: f32, exit_code_correct: bool, valgrind_clean: Option<bool>) -> f32 {
+        match self.mode {
+            ScoringMode::Binary => if passed { max_score } else { 0.0 },
+            ScoringMode::Partial => {
+                let output = self.output.unwrap_or(0.8);
+                let exit_code = self.exit_code.unwrap_or(0.1);
+                let valgrind = if valgrind_clean.is_some() { self.valgrind.unwrap_or(0.1) } else { 0.0 };
+
+                let achieved = output * similarity
+                    + if exit_code_correct { exit_code } else { 0.0 }
+                    + if valgrind_clean.unwrap_or(false) { valgrind } else { 0.0 };
+                let total = output + exit_code + valgrind;
+                if total > 0.0 {
+                    round_score(max_score * achieved / total)
+                }
+                else {
+                    0.0
+                }
+            },
+        }
+    }
+}
+
+/// Average similarity of all compared outputs, ignoring those not compared.
+pub fn similarity(distances: &[Option<f32>]) -> f32 {
+    let distances = distances.iter().flatten().collect::<Vec<&f32>>();
+    distances.iter().cloned().sum::<f32>() / distances.len() as f32
+}
+
+pub fn round_score(score: f32) -> f32 {
+    // adding zero turns the -0 of an empty sum into 0
+    (score * 100.0).round() / 100.0 + 0.0
+}
+
+// rounding again after the conversion avoids values like 13.15999984741211 in the JSON report
+pub fn json_score(score: f32) -> f64 {
+    (score as f64 * 100.0).round() / 100.0
+}
@@ -3,15 +3,21 @@ use std::sync::Weak;
 use std::time::Duration;
 use std::{fmt, io::Read};
 
+use serde::{Deserialize as _, Deserializer};
 use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::project::binary::Binary;
 use crate::project::definition::ProjectDefinition;
 use crate::test::io_test::parse_vg_log;
-use crate::testresult::testresult::Testresult;
+use crate::testresult::not_run_testresult::{NotRunReason, NotRunTestresult};
+use crate::testresult::testresult::{Attempt, Testresult};
 use crate::testrunner::{TestrunnerError, TestrunnerOptions};
+use super::criteria::{Evaluation, PassCriteria};
 use super::diff::{diff_plaintext, ChangesetInline, ChangesetFlat, diff_binary};
+use super::normalize::Normalization;
+use super::sandbox::Sandbox;
+use super::scoring::round_score;
 
 
 #[derive(Debug, Error)]
@@ -30,6 +36,12 @@ pub enum TestingError {
     InFileNotFound(String),
     #[error("output-file not found: {0}")]
     OutFileNotFound(String),
+    #[error("fixture not found: {0}")]
+    FixtureNotFound(String),
+    #[error("namespace isolation unavailable: {0}")]
+    IsolationUnavailable(String),
+    #[error("invalid regex in reference output: {0}")]
+    InvalidRegex(#[from] regex::Error),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error("internal i/o error: i/o mismatch")]
@@ -64,12 +76,253 @@ impl Default for DiffKind {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum MatchMode {
+    #[serde(alias = "exact")]
+    Exact,
+    #[serde(alias = "regex")]
+    Regex,
+    #[serde(alias = "lines-regex")]
+    LinesRegex,
+}
+
+impl Default for MatchMode {
+    fn default() -> MatchMode {
+        MatchMode::Exact
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub enum Diff {
     PlainText(Vec<ChangesetInline<String>>, f32),
     Binary(Vec<ChangesetFlat<Vec<u8>>>, f32),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ExitOutcome {
+    Exited(i32),
+    /// Signal number, and whether a core dump was written (as reported by the wait status).
+    Signaled(u8, bool),
+    TimedOut,
+    Killed,
+    /// the testcase was not run, see `NotRunReason`
+    NotRun,
+}
+
+impl ExitOutcome {
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            ExitOutcome::Exited(exit_code) => Some(*exit_code),
+            _ => None,
+        }
+    }
+
+    pub fn signal(&self) -> Option<u8> {
+        match self {
+            ExitOutcome::Signaled(signal, _) => Some(*signal),
+            _ => None,
+        }
+    }
+
+    pub fn had_timeout(&self) -> bool {
+        match self {
+            ExitOutcome::TimedOut | ExitOutcome::Killed => true,
+            _ => false,
+        }
+    }
+
+    pub fn matches(&self, exp_exit_code: Option<i32>, exp_signal: Option<u8>) -> bool {
+        match exp_signal {
+            Some(exp_signal) => self.signal() == Some(exp_signal),
+            None => self.exit_code() == Some(exp_exit_code.unwrap_or(0)),
+        }
+    }
+}
+
+impl From<subprocess::ExitStatus> for ExitOutcome {
+    fn from(status: subprocess::ExitStatus) -> Self {
+        match status {
+            subprocess::ExitStatus::Exited(exit_code) => ExitOutcome::Exited(exit_code as i32),
+            subprocess::ExitStatus::Other(exit_code) => ExitOutcome::Exited(exit_code),
+            // only the launcher is waited for by `subprocess`, which does not dump core
+            subprocess::ExitStatus::Signaled(signal) => ExitOutcome::Signaled(signal, false),
+            subprocess::ExitStatus::Undetermined => ExitOutcome::Killed,
+        }
+    }
+}
+
+impl fmt::Display for ExitOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitOutcome::Exited(exit_code) => write!(f, "{}", exit_code),
+            ExitOutcome::Signaled(signal, _) => write!(f, "{}", signal_name(*signal)),
+            ExitOutcome::TimedOut => write!(f, "timeout"),
+            ExitOutcome::Killed => write!(f, "killed"),
+            ExitOutcome::NotRun => write!(f, "not run"),
+        }
+    }
+}
+
+// signal numbers as used on Linux (x86, ARM)
+const SIGNAL_NAMES: [&str; 31] = [
+    "SIGHUP", "SIGINT", "SIGQUIT", "SIGILL", "SIGTRAP", "SIGABRT", "SIGBUS", "SIGFPE",
+    "SIGKILL", "SIGUSR1", "SIGSEGV", "SIGUSR2", "SIGPIPE", "SIGALRM", "SIGTERM", "SIGSTKFLT",
+    "SIGCHLD", "SIGCONT", "SIGSTOP", "SIGTSTP", "SIGTTIN", "SIGTTOU", "SIGURG", "SIGXCPU",
+    "SIGXFSZ", "SIGVTALRM", "SIGPROF", "SIGWINCH", "SIGIO", "SIGPWR", "SIGSYS",
+];
+
+pub fn signal_name(signal: u8) -> String {
+    match SIGNAL_NAMES.get((signal as usize).wrapping_sub(1)) {
+        Some(name) => name.to_string(),
+        None => format!("signal {}", signal),
+    }
+}
+
+pub fn parse_signal(name: &str) -> Option<u8> {
+    let name = name.trim().to_uppercase();
+    if let Ok(signal) = name.parse::<u8>() {
+        return Some(signal);
+    }
+    let name = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+    SIGNAL_NAMES.iter().position(|e| *e == name).map(|pos| pos as u8 + 1)
+}
+
+pub fn deserialize_signal<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+    where D: Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Signal {
+        Number(u8),
+        Name(String),
+    }
+
+    match Option::<Signal>::deserialize(deserializer)? {
+        Some(Signal::Number(signal)) => Ok(Some(signal)),
+        Some(Signal::Name(name)) => match parse_signal(&name) {
+            Some(signal) => Ok(Some(signal)),
+            None => Err(serde::de::Error::custom(format!("unknown signal: {}", name))),
+        },
+        None => Ok(None),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceLimits {
+    /// data segment size in MiB
+    pub memory: Option<u64>,
+    /// cpu time in seconds
+    pub cpu: Option<u64>,
+    /// size of created files in MiB
+    pub fsize: Option<u64>,
+    pub nproc: Option<u64>,
+}
+
+/// Factor and additional MiB for the memory limit if running under valgrind, for its shadow memory and own allocations.
+const VALGRIND_MEMORY_FACTOR: u64 = 2;
+const VALGRIND_MEMORY_OVERHEAD: u64 = 512;
+/// Factor for the CPU limit if running under valgrind, which slows down programs by about 20 to 30 times.
+const VALGRIND_CPU_FACTOR: u64 = 30;
+
+/// Seconds the CPU time reported after exceeding the CPU limit may be below it, as it is accounted differently.
+const CPU_TIME_TOLERANCE: f64 = 0.05;
+
+impl ResourceLimits {
+    pub fn new(project_definition: &ProjectDefinition, meta: &TestMeta) -> Self {
+        let mut memory = meta.rlimit_memory.or(project_definition.rlimit_memory);
+        let mut cpu = meta.rlimit_cpu.or(project_definition.rlimit_cpu);
+        // valgrind runs the program within its own process, so it is limited as well and needs some headroom
+        if project_definition.use_valgrind.unwrap_or(true) {
+            memory = memory.map(|memory| memory * VALGRIND_MEMORY_FACTOR + VALGRIND_MEMORY_OVERHEAD);
+            cpu = cpu.map(|cpu| cpu * VALGRIND_CPU_FACTOR);
+        }
+        ResourceLimits {
+            memory,
+            cpu,
+            fsize: meta.rlimit_fsize.or(project_definition.rlimit_fsize),
+            nproc: meta.rlimit_nproc.or(project_definition.rlimit_nproc),
+        }
+    }
+
+    /// Arguments for the launcher of the tested program, parsed by `launcher::launch`.
+    pub fn launcher_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(memory) = self.memory {
+            args.push(format!("--memory={}", memory));
+        }
+        if let Some(cpu) = self.cpu {
+            args.push(format!("--cpu={}", cpu));
+        }
+        if let Some(fsize) = self.fsize {
+            args.push(format!("--fsize={}", fsize));
+        }
+        if let Some(nproc) = self.nproc {
+            args.push(format!("--nproc={}", nproc));
+        }
+        args
+    }
+
+    /// Sets the limits for the current process, right before it executes the tested program.
+    pub fn apply(&self) -> std::io::Result<()> {
+        let set_rlimit = |resource, soft: u64, hard: u64| {
+            let rlimit = libc::rlimit { rlim_cur: soft as libc::rlim_t, rlim_max: hard as libc::rlim_t };
+            match unsafe { libc::setrlimit(resource, &rlimit) } {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            }
+        };
+        if let Some(memory) = self.memory {
+            set_rlimit(libc::RLIMIT_DATA, memory * 1024 * 1024, memory * 1024 * 1024)?;
+        }
+        if let Some(cpu) = self.cpu {
+            // hard limit one second above the soft limit, so SIGXCPU gets delivered before SIGKILL
+            set_rlimit(libc::RLIMIT_CPU, cpu, cpu + 1)?;
+        }
+        if let Some(fsize) = self.fsize {
+            set_rlimit(libc::RLIMIT_FSIZE, fsize * 1024 * 1024, fsize * 1024 * 1024)?;
+        }
+        if let Some(nproc) = self.nproc {
+            set_rlimit(libc::RLIMIT_NPROC, nproc, nproc)?;
+        }
+        Ok(())
+    }
+
+    /// Which limit the program exceeded, given whether it ended as expected and the CPU time it used (see `launcher::LaunchStatus`).
+    pub fn exceeded(&self, exit_outcome: ExitOutcome, exit_code_correct: bool, cpu_time: Option<f64>) -> Option<LimitExceeded> {
+        // reaching the soft limit raises SIGXCPU, even if the program handles it and exits normally
+        if let (Some(cpu), Some(cpu_time)) = (self.cpu, cpu_time) {
+            if cpu_time >= cpu as f64 - CPU_TIME_TOLERANCE {
+                return Some(LimitExceeded::Cpu);
+            }
+        }
+        if self.fsize.is_some() && exit_outcome.signal().map(signal_name).as_deref() == Some("SIGXFSZ") {
+            return Some(LimitExceeded::FileSize);
+        }
+        // failing allocations do not terminate the program by themselves, but make it fail somehow
+        if self.memory.is_some() && !exit_code_correct && !exit_outcome.had_timeout() {
+            return Some(LimitExceeded::Memory);
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum LimitExceeded {
+    Memory,
+    Cpu,
+    FileSize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Memory => write!(f, "memory limit exceeded"),
+            LimitExceeded::Cpu => write!(f, "CPU limit exceeded"),
+            LimitExceeded::FileSize => write!(f, "file size limit exceeded"),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TestMeta {
     #[serde(skip)]
@@ -77,12 +330,24 @@ pub struct TestMeta {
     pub name: String,
     pub description: Option<String>,
     pub timeout: Option<u64>,
+    pub retries: Option<u32>,
     #[serde(default)]
     pub add_diff_mode: DiffKind,
     pub add_out_file: Option<String>,
     pub add_exp_file: Option<String>,
     #[serde(default)]
     pub protected: bool,
+    pub tags: Option<Vec<String>>,
+    pub sandbox: Option<bool>,
+    pub fixtures: Option<Vec<String>>,
+    pub normalize: Option<Vec<Normalization>>,
+    pub rlimit_memory: Option<u64>,
+    pub rlimit_cpu: Option<u64>,
+    pub rlimit_fsize: Option<u64>,
+    pub rlimit_nproc: Option<u64>,
+    pub points: Option<f32>,
+    pub weight: Option<f32>,
+    pub pass_criteria: Option<PassCriteria>,
 }
 
 pub trait Test : erased_serde::Serialize {
@@ -98,9 +363,9 @@ pub trait Test : erased_serde::Serialize {
     fn deserialize_trait<'de, D: ?Sized>(deserializer: &mut dyn erased_serde::Deserializer<'de>) -> Result<Box<dyn Test + Send + Sync>, erased_serde::Error>
         where Self: Sized;
 
-    fn did_pass(&self, exp_exit_code: Option<i32>, exit_code: Option<i32>, distance: f32, add_distance: f32, had_timeout: bool) -> bool {
-        exit_code.is_some() && exit_code.unwrap() == exp_exit_code.unwrap_or(0)
-            && distance == 1.0 && add_distance == 1.0 && !had_timeout
+    fn did_pass(&self, project_definition: &ProjectDefinition, evaluation: &Evaluation) -> bool {
+        PassCriteria::merge(self.get_test_meta().pass_criteria.as_ref(), project_definition.pass_criteria.as_ref())
+            .evaluate(evaluation)
     }
 
     fn get_valgrind_result(&self, project_definition: &ProjectDefinition, options: &TestrunnerOptions, basedir: &str, vg_log_folder: &str, vg_filepath: &str) -> Result<(Option<i32>, Option<i32>), TestingError> {
@@ -137,9 +402,48 @@ pub trait Test : erased_serde::Serialize {
         Ok((mem_leaks, mem_errors))
     }
 
-    fn get_add_diff(&self) -> Result<(Option<Diff>, f32, bool), TestingError> {
+    /// Runs the testcase and reruns it while it fails, up to `retries` times. All attempts are recorded in the result.
+    fn run_with_retries(&self, project_definition: &ProjectDefinition) -> Result<Box<dyn Testresult + Send + Sync>, TestingError> {
+        let retries = self.get_test_meta().retries.or(project_definition.retries).unwrap_or(0) as usize;
+        let mut attempts = Vec::with_capacity(1);
+        loop {
+            let mut result = self.run()?;
+            attempts.push(Attempt::new(result.as_ref()));
+            if result.passed() || attempts.len() > retries {
+                result.set_attempts(attempts);
+                return Ok(result);
+            }
+        }
+    }
+
+    fn get_max_score(&self) -> f32 {
+        let meta = self.get_test_meta();
+        round_score(meta.points.unwrap_or(1.0) * meta.weight.unwrap_or(1.0))
+    }
+
+    /// Result of a testcase, which is not run.
+    fn not_run(&self, reason: NotRunReason) -> Box<dyn Testresult + Send + Sync> {
+        let kind = match self.type_id() {
+            "OrdIO" => TestcaseType::OrdIOTest,
+            _ => TestcaseType::IOTest,
+        };
+        Box::new(NotRunTestresult::new(kind, reason, self.get_test_meta(), self.get_max_score()))
+    }
+
+    fn get_score(&self, project_definition: &ProjectDefinition, passed: bool, exit_code_correct: bool, similarity: f32, mem_leaks: Option<i32>, mem_errors: Option<i32>) -> (f32, f32) {
+        let max_score = self.get_max_score();
+        let valgrind_clean = match (mem_leaks, mem_errors) {
+            (Some(mem_leaks), Some(mem_errors)) => Some(mem_leaks == 0 && mem_errors == 0),
+            _ => None,
+        };
+        let score = project_definition.scoring.clone().unwrap_or_default()
+            .score(max_score, passed, similarity, exit_code_correct, valgrind_clean);
+        (score, max_score)
+    }
+
+    fn get_add_diff(&self, sandbox: Option<&Sandbox>) -> Result<(Option<Diff>, f32, bool), TestingError> {
         let add_file_missing;
-        let add_diff = match self.calc_add_diff() {
+        let add_diff = match self.calc_add_diff(sandbox) {
             Ok(ok) => {
                 add_file_missing = false;
                 ok
@@ -167,15 +471,20 @@ pub trait Test : erased_serde::Serialize {
         Ok((add_diff, add_distance, add_file_missing))
     }
 
-    fn calc_add_diff(&self) -> Result<Option<Diff>, TestingError> {
+    fn calc_add_diff(&self, sandbox: Option<&Sandbox>) -> Result<Option<Diff>, TestingError> {
         let test_meta = self.get_test_meta();
 
         if test_meta.add_out_file.is_some() && test_meta.add_exp_file.is_some() {
+            // the program's working directory is the sandbox, if used
+            let out_path = match sandbox {
+                Some(sandbox) => sandbox.resolve(test_meta.add_out_file.as_ref().unwrap()),
+                None => test_meta.add_out_file.clone().unwrap(),
+            };
             match test_meta.add_diff_mode {
                 DiffKind::PlainText => {
                     let ref_file = read_to_string(test_meta.add_exp_file.as_ref().unwrap())
                         .map_err(|_| TestingError::RefFileNotFound(test_meta.add_exp_file.as_ref().unwrap().clone()))?;
-                    let out_file = read_to_string(test_meta.add_out_file.as_ref().unwrap())
+                    let out_file = read_to_string(&out_path)
                         .map_err(|_| TestingError::OutFileNotFound(test_meta.add_out_file.as_ref().unwrap().clone()))?;
 
                     let (diff, distance) = diff_plaintext(&ref_file, &out_file, Duration::from_secs(20));
@@ -184,7 +493,7 @@ pub trait Test : erased_serde::Serialize {
                 DiffKind::Binary => {
                     let mut ref_fd = File::open(test_meta.add_exp_file.as_ref().unwrap())
                         .map_err(|_| TestingError::RefFileNotFound(test_meta.add_exp_file.as_ref().unwrap().clone()))?;
-                    let mut out_fd = File::open(test_meta.add_out_file.as_ref().unwrap())
+                    let mut out_fd = File::open(&out_path)
                         .map_err(|_| TestingError::OutFileNotFound(test_meta.add_out_file.as_ref().unwrap().clone()))?;
 
                     let mut ref_buf = Vec::<u8>::new();
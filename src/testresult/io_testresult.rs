@@ -5,10 +5,11 @@ use serde_derive::Serialize;
 use serde_json::json;
 
 use crate::project::definition::ProjectDefinition;
-use crate::test::diff::{ChangesetInline, textdiff_to_html, binarydiff_to_html, with_ws_hints};
-use crate::test::test::{TestcaseType, Diff};
+use crate::test::diff::{ChangesetInline, textdiff_to_html, textdiff_to_unified, binarydiff_to_html, with_ws_hints};
+use crate::test::scoring::json_score;
+use crate::test::test::{TestcaseType, Diff, ExitOutcome, LimitExceeded, signal_name};
 use crate::testrunner::{TestrunnerError, TestrunnerOptions};
-use super::testresult::Testresult;
+use super::testresult::{Attempt, Testresult, markdown_entry};
 
 
 #[derive(Clone, Serialize, TemplateOnce)]
@@ -21,6 +22,9 @@ pub struct IoTestresult {
     pub protected: bool,
     pub diff: Vec<ChangesetInline<String>>,
     pub diff_distance: f32,
+    pub raw_output: Option<String>,
+    pub stderr_diff: Option<Vec<ChangesetInline<String>>>,
+    pub stderr_distance: Option<f32>,
     pub add_diff: Option<Diff>,
     pub add_distance: Option<f32>,
     pub add_file_missing: bool,
@@ -30,9 +34,14 @@ pub struct IoTestresult {
     pub mem_logfile: String,
     pub command_used: String,
     pub timeout: bool,
-    pub exit_code: Option<i32>,
+    pub limit_exceeded: Option<LimitExceeded>,
+    pub exit_outcome: ExitOutcome,
     pub expected_exit_code: Option<i32>,
+    pub expected_signal: Option<u8>,
     pub passed: bool,
+    pub score: f32,
+    pub max_score: f32,
+    pub attempts: Vec<Attempt>,
     pub input: String,
     #[serde(skip)]
     pub project_definition: Weak<ProjectDefinition>,
@@ -57,6 +66,22 @@ impl Testresult for IoTestresult {
         self.passed
     }
 
+    fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    fn set_attempts(&mut self, attempts: Vec<Attempt>) {
+        self.attempts = attempts;
+    }
+
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn max_score(&self) -> f32 {
+        self.max_score
+    }
+
     fn protected(&self) -> bool {
         self.protected
     }
@@ -65,6 +90,10 @@ impl Testresult for IoTestresult {
         self.timeout
     }
 
+    fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        self.limit_exceeded
+    }
+
     fn truncated_output(&self) -> bool {
         self.truncated_output
     }
@@ -81,14 +110,18 @@ impl Testresult for IoTestresult {
         self.mem_logfile.clone()
     }
 
-    fn exit_code(&self) -> Option<i32> {
-        self.exit_code
+    fn exit_outcome(&self) -> ExitOutcome {
+        self.exit_outcome
     }
 
     fn expected_exit_code(&self) -> Option<i32> {
         self.expected_exit_code
     }
 
+    fn expected_signal(&self) -> Option<u8> {
+        self.expected_signal
+    }
+
     fn diff_distance(&self) -> f32 {
         self.diff_distance
     }
@@ -97,23 +130,44 @@ impl Testresult for IoTestresult {
         self.add_distance
     }
 
+    fn stderr_diff_distance(&self) -> Option<f32> {
+        self.stderr_distance
+    }
+
+    fn add_file_missing(&self) -> bool {
+        self.add_file_missing
+    }
+
     fn get_json_entry(&self) -> Result<serde_json::Value, TestrunnerError> {
         Ok(json!({
             "kind": self.kind.to_string(),
             "name": self.name,
             "description": self.description,
             "passed": self.passed,
+            "skipped": false,
+            "flaky": self.flaky(),
+            "attempts": self.attempts,
+            "score": json_score(self.score),
+            "max_score": json_score(self.max_score),
             "diff": self.diff,
             "diff_distance": self.diff_distance,
             "add_diff": self.add_diff,
             "add_diff_distance": self.add_distance.unwrap_or(-1.0),
             "add_file_missing": self.add_file_missing,
+            "stderr_diff": self.stderr_diff,
+            "stderr_diff_distance": self.stderr_distance.unwrap_or(-1.0),
             "truncated_output": self.truncated_output,
             "command_used": self.command_used,
-            "exit_code": self.exit_code.unwrap_or(0),
+            "exit_code": self.exit_outcome.exit_code().unwrap_or(0),
+            "exit_outcome": self.exit_outcome.to_string(),
+            "core_dumped": matches!(self.exit_outcome, ExitOutcome::Signaled(_, true)),
+            "signal": self.exit_outcome.signal().map(signal_name),
+            "expected_signal": self.expected_signal.map(signal_name),
             "mem_leaks": self.mem_leaks.unwrap_or(-1),
             "mem_errors": self.mem_errors.unwrap_or(-1),
             "timeout": self.timeout,
+            "raw_output": self.raw_output,
+            "limit_exceeded": self.limit_exceeded.map(|e| e.to_string()),
             "input": self.input,
             "protected" : self.protected,
         }))
@@ -122,5 +176,26 @@ impl Testresult for IoTestresult {
     fn get_html_entry_detailed(&self) -> Result<String, TestrunnerError> {
         Ok(self.clone().render_once()?)
     }
+
+    fn unified_diffs(&self, color: bool) -> Vec<(&'static str, String)> {
+        let mut diffs = vec![];
+        if self.diff_distance < 1.0 {
+            diffs.push(("Output", textdiff_to_unified(&self.diff, 3, color)));
+        }
+        match (&self.stderr_diff, self.stderr_distance) {
+            (Some(stderr_diff), Some(distance)) if distance < 1.0 => diffs.push(("Error output", textdiff_to_unified(stderr_diff, 3, color))),
+            _ => {},
+        }
+        match &self.add_diff {
+            Some(Diff::PlainText(add_diff, distance)) if *distance < 1.0 => diffs.push(("File", textdiff_to_unified(add_diff, 3, color))),
+            Some(Diff::Binary(_, distance)) if *distance < 1.0 => diffs.push(("File", "Binary files differ\n".to_owned())),
+            _ => {},
+        }
+        diffs
+    }
+
+    fn get_markdown_entry_detailed(&self) -> String {
+        markdown_entry(self, &self.command_used)
+    }
 }
 
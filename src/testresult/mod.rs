@@ -1,4 +1,5 @@
 pub mod testresult;
 pub mod io_testresult;
 pub mod ordio_testresult;
+pub mod not_run_testresult;
 
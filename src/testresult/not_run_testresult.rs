@@ -0,0 +1,177 @@
+use sailfish::TemplateOnce;
+use serde_derive::Serialize;
+use serde_json::json;
+
+use crate::test::scoring::json_score;
+use crate::test::test::{TestcaseType, ExitOutcome, LimitExceeded, TestMeta};
+use crate::testrunner::TestrunnerError;
+use super::testresult::{Attempt, Testresult, markdown_escape};
+
+
+/// Why a testcase was not run, see `Testresult::status` for how it counts.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum NotRunReason {
+    /// the run was cancelled after too many failed testcases
+    MaxFailures,
+    /// a failing source check was violated
+    SourceChecks,
+}
+
+impl std::fmt::Display for NotRunReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NotRunReason::MaxFailures => write!(f, "too many failed testcases"),
+            NotRunReason::SourceChecks => write!(f, "source checks failed"),
+        }
+    }
+}
+
+/// A testcase that was not run.
+#[derive(Clone, Serialize, TemplateOnce)]
+#[template(path = "testreport_testcase_not_run.stpl")]
+pub struct NotRunTestresult {
+    pub kind: TestcaseType,
+    pub reason: NotRunReason,
+    pub number: i32,
+    pub name: String,
+    pub description: String,
+    pub protected: bool,
+    pub max_score: f32,
+}
+
+impl NotRunTestresult {
+    pub fn new(kind: TestcaseType, reason: NotRunReason, meta: &TestMeta, max_score: f32) -> Self {
+        NotRunTestresult {
+            kind,
+            reason,
+            number: meta.number,
+            name: meta.name.clone(),
+            description: meta.description.clone().unwrap_or_default(),
+            protected: meta.protected,
+            max_score,
+        }
+    }
+}
+
+impl Testresult for NotRunTestresult {
+    fn kind(&self) -> TestcaseType {
+        self.kind
+    }
+
+    fn number(&self) -> i32 {
+        self.number
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn passed(&self) -> bool {
+        false
+    }
+
+    fn not_run_reason(&self) -> Option<NotRunReason> {
+        Some(self.reason)
+    }
+
+    fn attempts(&self) -> &[Attempt] {
+        &[]
+    }
+
+    fn set_attempts(&mut self, _attempts: Vec<Attempt>) {}
+
+    fn score(&self) -> f32 {
+        0.0
+    }
+
+    fn max_score(&self) -> f32 {
+        self.max_score
+    }
+
+    fn protected(&self) -> bool {
+        self.protected
+    }
+
+    fn timeout(&self) -> bool {
+        false
+    }
+
+    fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        None
+    }
+
+    fn truncated_output(&self) -> bool {
+        false
+    }
+
+    fn mem_leaks(&self) -> Option<i32> {
+        None
+    }
+
+    fn mem_errors(&self) -> Option<i32> {
+        None
+    }
+
+    fn mem_logfile(&self) -> String {
+        String::new()
+    }
+
+    fn exit_outcome(&self) -> ExitOutcome {
+        ExitOutcome::NotRun
+    }
+
+    fn expected_exit_code(&self) -> Option<i32> {
+        None
+    }
+
+    fn expected_signal(&self) -> Option<u8> {
+        None
+    }
+
+    fn diff_distance(&self) -> f32 {
+        0.0
+    }
+
+    fn add_diff_distance(&self) -> Option<f32> {
+        None
+    }
+
+    fn stderr_diff_distance(&self) -> Option<f32> {
+        None
+    }
+
+    fn add_file_missing(&self) -> bool {
+        false
+    }
+
+    fn failure_reasons(&self) -> Vec<String> {
+        vec![format!("not run: {}", self.reason)]
+    }
+
+    fn get_json_entry(&self) -> Result<serde_json::Value, TestrunnerError> {
+        Ok(json!({
+            "kind": self.kind.to_string(),
+            "name": self.name,
+            "description": self.description,
+            "passed": false,
+            "skipped": self.skipped(),
+            "not_run_reason": self.reason.to_string(),
+            "score": 0.0,
+            "max_score": json_score(self.max_score),
+            "protected" : self.protected,
+        }))
+    }
+
+    fn get_html_entry_detailed(&self) -> Result<String, TestrunnerError> {
+        Ok(self.clone().render_once()?)
+    }
+
+    fn unified_diffs(&self, _color: bool) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+
+    fn get_markdown_entry_detailed(&self) -> String {
+        format!("<details>\n<summary>#{:0>2}: {} &ndash; {}</summary>\n\nNot run: {}.\n</details>\n",
+            self.number, markdown_escape(&self.name), if self.skipped() { "skipped" } else { "failed" }, self.reason)
+    }
+}
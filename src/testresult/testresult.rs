@@ -1,6 +1,18 @@
-use crate::test::test::TestcaseType;
+use serde_derive::Serialize;
+
+use crate::test::scoring::{json_score, similarity};
+use crate::test::test::{ExitOutcome, LimitExceeded, TestcaseType, signal_name};
 use crate::testrunner::TestrunnerError;
+use super::not_run_testresult::NotRunReason;
+
 
+/// Outcome of a testcase, as counted in the summaries and testreports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed,
+    Skipped,
+}
 
 pub trait Testresult {
     fn kind(&self) -> TestcaseType;
@@ -11,10 +23,45 @@ pub trait Testresult {
 
     fn passed(&self) -> bool;
 
+    /// Testcases not run because of failing source checks count as failed, as the submission is at fault.
+    fn status(&self) -> Status {
+        match self.not_run_reason() {
+            Some(NotRunReason::MaxFailures) => Status::Skipped,
+            Some(NotRunReason::SourceChecks) => Status::Failed,
+            None if self.passed() => Status::Passed,
+            None => Status::Failed,
+        }
+    }
+
+    fn skipped(&self) -> bool {
+        self.status() == Status::Skipped
+    }
+
+    /// Why the testcase was not run, `None` if it was run.
+    fn not_run_reason(&self) -> Option<NotRunReason> {
+        None
+    }
+
+    /// All runs of the testcase, the last one being this result.
+    fn attempts(&self) -> &[Attempt];
+
+    fn set_attempts(&mut self, attempts: Vec<Attempt>);
+
+    /// Passed, but only after failing at first.
+    fn flaky(&self) -> bool {
+        self.passed() && self.attempts().len() > 1
+    }
+
+    fn score(&self) -> f32;
+
+    fn max_score(&self) -> f32;
+
     fn protected(&self) -> bool;
 
     fn timeout(&self) -> bool;
 
+    fn limit_exceeded(&self) -> Option<LimitExceeded>;
+
     fn truncated_output(&self) -> bool;
 
     fn mem_leaks(&self) -> Option<i32>;
@@ -23,16 +70,158 @@ pub trait Testresult {
 
     fn mem_logfile(&self) -> String;
 
-    fn exit_code(&self) -> Option<i32>;
+    fn exit_outcome(&self) -> ExitOutcome;
+
+    fn exit_code(&self) -> Option<i32> {
+        self.exit_outcome().exit_code()
+    }
 
     fn expected_exit_code(&self) -> Option<i32>;
 
+    fn expected_signal(&self) -> Option<u8>;
+
+    fn exit_code_correct(&self) -> bool {
+        self.exit_outcome().matches(self.expected_exit_code(), self.expected_signal())
+    }
+
     fn diff_distance(&self) -> f32;
 
     fn add_diff_distance(&self) -> Option<f32>;
 
+    fn stderr_diff_distance(&self) -> Option<f32>;
+
+    fn add_file_missing(&self) -> bool;
+
+    // average similarity of all compared outputs
+    fn similarity(&self) -> f32 {
+        similarity(&[Some(self.diff_distance()), self.add_diff_distance(), self.stderr_diff_distance()])
+    }
+
+    // short descriptions of everything that did not match the expectations
+    fn failure_reasons(&self) -> Vec<String> {
+        let percent = |distance: f32| (distance * 1000.0).floor() / 10.0;
+        let mut reasons = Vec::new();
+        if self.timeout() {
+            reasons.push("timeout".to_owned());
+        }
+        if let Some(limit_exceeded) = self.limit_exceeded() {
+            reasons.push(limit_exceeded.to_string());
+        }
+        if self.diff_distance() < 1.0 {
+            reasons.push(format!("output similarity: {}%", percent(self.diff_distance())));
+        }
+        match self.stderr_diff_distance() {
+            Some(distance) if distance < 1.0 => reasons.push(format!("error output similarity: {}%", percent(distance))),
+            _ => {},
+        }
+        if self.add_file_missing() {
+            reasons.push("file not found".to_owned());
+        }
+        else {
+            match self.add_diff_distance() {
+                Some(distance) if distance < 1.0 => reasons.push(format!("file similarity: {}%", percent(distance))),
+                _ => {},
+            }
+        }
+        if !self.timeout() && !self.exit_code_correct() {
+            let expected = match self.expected_signal() {
+                Some(signal) => signal_name(signal),
+                None => self.expected_exit_code().unwrap_or(0).to_string(),
+            };
+            reasons.push(format!("exit code: expected {}, got {}", expected, self.exit_outcome()));
+        }
+        match (self.mem_errors(), self.mem_leaks()) {
+            (Some(mem_errors), Some(mem_leaks)) if mem_errors > 0 || mem_leaks > 0 => {
+                reasons.push(format!("valgrind: {} memory usage errors, {} memory leaks", mem_errors, mem_leaks));
+            },
+            _ => {},
+        }
+        reasons
+    }
+
     fn get_json_entry(&self) -> Result<serde_json::Value, TestrunnerError>;
 
     fn get_html_entry_detailed(&self) -> Result<String, TestrunnerError>;
+
+    /// Unified diffs of all outputs that differ, titled by the kind of output.
+    fn unified_diffs(&self, color: bool) -> Vec<(&'static str, String)>;
+
+    fn get_markdown_entry_detailed(&self) -> String;
+}
+
+/// Summary of a single run of a testcase.
+#[derive(Clone, Debug, Serialize)]
+pub struct Attempt {
+    pub passed: bool,
+    pub score: f64,
+    pub similarity: f64,
+    pub exit_outcome: String,
+    pub timeout: bool,
+    pub failure_reasons: Vec<String>,
+}
+
+impl Attempt {
+    pub fn new<T: Testresult + ?Sized>(tc: &T) -> Self {
+        Attempt {
+            passed: tc.passed(),
+            score: json_score(tc.score()),
+            similarity: json_score(tc.similarity()),
+            exit_outcome: tc.exit_outcome().to_string(),
+            timeout: tc.timeout(),
+            failure_reasons: tc.failure_reasons(),
+        }
+    }
+}
+
+/// Collapsible section of the markdown report, with the unified diffs of all outputs that differ.
+pub fn markdown_entry<T: Testresult + ?Sized>(tc: &T, command_used: &str) -> String {
+    let mut entry = format!("<details>\n<summary>#{:0>2}: {} &ndash; {}</summary>\n\n",
+        tc.number(), markdown_escape(&tc.name()), if tc.passed() { "passed" } else { "failed" });
+    entry.push_str(&format!("Commandline: {}\n\n", markdown_code(command_used)));
+    if tc.attempts().len() > 1 {
+        entry.push_str(&format!("Attempts: {}{}\n\n", tc.attempts().len(), if tc.flaky() { " (flaky)" } else { "" }));
+    }
+    let reasons = tc.failure_reasons();
+    reasons.iter().for_each(|reason| entry.push_str(&format!("- {}\n", markdown_escape(reason))));
+    if !reasons.is_empty() {
+        entry.push('\n');
+    }
+    tc.unified_diffs(false).iter().for_each(|(title, diff)| {
+        entry.push_str(&format!("{}:\n\n{}\n", title, markdown_code_block("diff", diff)));
+    });
+    entry.push_str("</details>\n");
+    entry
+}
+
+pub fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    text.chars().for_each(|c| {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    });
+    escaped
+}
+
+// the fence has to be longer than any run of backticks in the text
+fn backtick_fence(text: &str, min: usize) -> String {
+    let longest = text.split(|c| c != '`').map(|run| run.len()).max().unwrap_or(0);
+    "`".repeat((longest + 1).max(min))
+}
+
+pub fn markdown_code(text: &str) -> String {
+    let fence = backtick_fence(text, 1);
+    format!("{} {} {}", fence, text.replace('\n', " "), fence)
+}
+
+pub fn markdown_code_block(lang: &str, text: &str) -> String {
+    let fence = backtick_fence(text, 3);
+    let newline = if text.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}\n{}{}{}\n", fence, lang, text, newline, fence)
 }
 
@@ -1,20 +1,29 @@
 use std::collections::{HashMap, BTreeMap};
 use std::fs::read_to_string;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crossbeam::scope;
 use pariter::IteratorExt;
+use regex::Regex;
 use sailfish::{TemplateOnce, RenderError};
 use serde::{Deserializer, Deserialize};
+use serde_json::json;
 use serde_tagged::de::BoxFnSeed;
 use thiserror::Error;
 
+use crate::events::EventLog;
 use crate::project::binary::{Binary, CompileError};
+use crate::project::build::Build;
 use crate::project::definition::ProjectDefinition;
-use crate::test::io_test::IoTest;
+use crate::project::source_checks::{SourceCheckError, Violation, run_source_checks};
+use crate::test::io_test::{IoTest, check_namespace_availability};
 use crate::test::ordio_test::OrdIoTest;
-use crate::test::test::{Test, TestingError};
-use crate::testresult::testresult::Testresult;
+use crate::test::scoring::{json_score, round_score};
+use crate::test::test::{ExitOutcome, Test, TestMeta, TestingError, signal_name};
+use crate::testresult::not_run_testresult::NotRunReason;
+use crate::testresult::testresult::{Testresult, markdown_code_block, markdown_code, markdown_escape};
 
 
 #[derive(Debug, Error)]
@@ -29,18 +38,126 @@ pub enum TestrunnerError {
     CompileError(#[from] CompileError),
     #[error(transparent)]
     TestingError(#[from] TestingError),
+    #[error(transparent)]
+    SourceCheckError(#[from] SourceCheckError),
     #[error("failed rendering testreport: {}", .0.to_string())]
     RenderError(#[from] RenderError),
+    #[error("no testcases match the selection")]
+    NoTestcasesSelected,
+    #[error("failed opening event log {0}: {1}")]
+    EventLogError(String, String),
     #[error("failed generating JSON: {}", .0.to_string())]
     JSONGenerationError(#[from] serde_json::Error),
 }
 
+pub const EXIT_PASSED: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_CONFIG_ERROR: i32 = 2;
+pub const EXIT_COMPILATION_FAILED: i32 = 3;
+pub const EXIT_INTERNAL_ERROR: i32 = 4;
+pub const EXIT_BUILD_TIMEOUT: i32 = 5;
+
+impl TestrunnerError {
+    /// Exit code of the testrunner, if it is aborted by this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TestrunnerError::ConfigNotFound(_)
+                | TestrunnerError::ConfigParseError(_)
+                | TestrunnerError::NoTestcasesSelected
+                | TestrunnerError::EventLogError(_, _)
+                | TestrunnerError::SourceCheckError(_) => EXIT_CONFIG_ERROR,
+            TestrunnerError::CompileError(CompileError::BuildCommandFailed(_, _)) => EXIT_INTERNAL_ERROR,
+            TestrunnerError::CompileError(CompileError::NoBuildCommands) => EXIT_CONFIG_ERROR,
+            TestrunnerError::CompileError(CompileError::Timeout(_, _)) => EXIT_BUILD_TIMEOUT,
+            TestrunnerError::CompileError(_) => EXIT_COMPILATION_FAILED,
+            TestrunnerError::TestingError(err) => match err {
+                TestingError::IoConfigNotFound(_)
+                    | TestingError::RefFileNotFound(_)
+                    | TestingError::InFileNotFound(_)
+                    | TestingError::FixtureNotFound(_)
+                    | TestingError::InvalidRegex(_) => EXIT_CONFIG_ERROR,
+                _ => EXIT_INTERNAL_ERROR,
+            },
+            _ => EXIT_INTERNAL_ERROR,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Isolation {
+    None,
+    /// unprivileged user, mount, network and PID namespaces
+    Namespaces,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    /// TAP version 14 on stdout, everything else goes to stderr
+    Tap,
+}
+
+/// Selects the testcases to run, all given criteria have to match.
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// regex on the name
+    pub filter: Option<Regex>,
+    pub only: Option<Vec<RangeInclusive<i32>>>,
+    /// at least one of the tags has to match
+    pub tags: Option<Vec<String>>,
+}
+
+impl Selection {
+    /// Parses a list of numbers and ranges, like `3,5-8`.
+    pub fn parse_numbers(list: &str) -> Result<Vec<RangeInclusive<i32>>, String> {
+        list.split(',').map(|range| {
+            let range = range.trim();
+            let parse = |num: &str| num.trim().parse::<i32>().map_err(|_| format!("not a (positive) number: {}", num.trim()));
+            match range.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!("invalid range: {}", range));
+                    }
+                    Ok(start..=end)
+                },
+                None => {
+                    let num = parse(range)?;
+                    Ok(num..=num)
+                },
+            }
+        }).collect()
+    }
+
+    /// Whether any of `--filter`, `--only` or `--tag` was given.
+    pub fn is_active(&self) -> bool {
+        self.filter.is_some() || self.only.is_some() || self.tags.is_some()
+    }
+
+    pub fn matches(&self, meta: &TestMeta) -> bool {
+        self.filter.as_ref().map_or(true, |filter| filter.is_match(&meta.name))
+            && self.only.as_ref().map_or(true, |only| only.iter().any(|range| range.contains(&meta.number)))
+            && self.tags.as_ref().map_or(true, |tags| {
+                meta.tags.as_ref().map_or(false, |tc_tags| tc_tags.iter().any(|tag| tags.contains(tag)))
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct TestrunnerOptions {
     pub protected_mode: bool,
     pub ws_hints: bool,
     pub sudo: Option<String>,
     pub jobs: usize,
+    pub keep_sandboxes: bool,
+    pub isolation: Isolation,
+    pub format: OutputFormat,
+    pub events: Option<EventLog>,
+    pub show_diff: bool,
+    pub color: bool,
+    pub selection: Selection,
+    /// remaining testcases are skipped after this many failed testcases
+    pub max_failures: Option<usize>,
 }
 
 impl Default for TestrunnerOptions {
@@ -50,6 +167,30 @@ impl Default for TestrunnerOptions {
             ws_hints: true,
             sudo: None,
             jobs: 0,
+            keep_sandboxes: false,
+            isolation: Isolation::None,
+            format: OutputFormat::Human,
+            events: None,
+            show_diff: false,
+            color: false,
+            selection: Selection::default(),
+            max_failures: None,
+        }
+    }
+}
+
+impl TestrunnerOptions {
+    /// Prints progress information, which must not end up between machine-readable output.
+    pub fn log(&self, message: &str) {
+        match self.format {
+            OutputFormat::Human => println!("{}", message),
+            OutputFormat::Tap => eprintln!("{}", message),
+        }
+    }
+
+    pub fn emit(&self, event: &str, payload: serde_json::Value) {
+        if let Some(events) = self.events.as_ref() {
+            events.emit(event, payload);
         }
     }
 }
@@ -61,10 +202,12 @@ pub struct Testrunner {
     project_definition: Arc<ProjectDefinition>,
     testcases: Vec<Box<dyn Test + Send + Sync>>,
     #[serde(skip)]
-    testresults: Vec<Box<dyn Testresult + Send + Sync>>,
+    testresults: Vec<Arc<dyn Testresult + Send + Sync>>,
     #[serde(skip)]
     binary: Arc<Binary>,
     #[serde(skip)]
+    violations: Vec<Violation>,
+    #[serde(skip)]
     options: Arc<TestrunnerOptions>,
 }
 
@@ -72,8 +215,12 @@ impl Testrunner {
     pub fn from_file(path: &str, options: TestrunnerOptions) -> Result<Self, TestrunnerError> {
         let config = read_to_string(path).map_err(|_| TestrunnerError::ConfigNotFound(path.to_string()))?;
         let mut runner: Self = toml::from_str(&config).map_err(|err| TestrunnerError::ConfigParseError(err.to_string()))?;
+        if options.isolation == Isolation::Namespaces {
+            check_namespace_availability()?;
+        }
+        runner.binary = Arc::new(Self::compile(&runner.project_definition, &options)?);
+        runner.violations = Self::check_sources(&runner.project_definition, &options, &runner.project_dir())?;
         runner.options = Arc::new(options);
-        runner.binary = Arc::new(Binary::from_project_definition(&runner.project_definition)?);
 
         let mut tc_number = 0;
         let project_definition = Arc::downgrade(&runner.project_definition);
@@ -83,9 +230,96 @@ impl Testrunner {
             tc_number += 1;
             tc.init(tc_number, project_definition.clone(), options.clone(), binary.clone())
         })?;
+
+        // numbers are assigned before selecting, so they stay the same for every selection
+        let options = Arc::clone(&runner.options);
+        runner.testcases.retain(|tc| options.selection.matches(tc.get_test_meta()));
+        if runner.testcases.is_empty() && runner.options.selection.is_active() {
+            return Err(TestrunnerError::NoTestcasesSelected);
+        }
         Ok(runner)
     }
 
+    fn compile(project_definition: &ProjectDefinition, options: &TestrunnerOptions) -> Result<Binary, CompileError> {
+        options.emit("compile_started", json!({}));
+        let binary = match Binary::from_project_definition(project_definition, options) {
+            // reported like a failed compilation, so the output of the build shows up in the testreports
+            Err(CompileError::Timeout(build_timeout, info)) => {
+                options.log(&format!("Error: building timed out after {} seconds", build_timeout));
+                Ok(Binary { info: *info })
+            },
+            binary => binary,
+        };
+        match &binary {
+            Ok(binary) => options.emit("compile_finished", json!({ "binary": &binary.info })),
+            Err(err) => options.emit("compile_finished", json!({ "error": err.to_string() })),
+        }
+        binary
+    }
+
+    /// Runs the source checks of the project, which are independent of the compilation.
+    fn check_sources(project_definition: &ProjectDefinition, options: &TestrunnerOptions, dir: &str) -> Result<Vec<Violation>, SourceCheckError> {
+        let checks = match project_definition.source_checks.as_ref() {
+            Some(checks) => checks,
+            None => return Ok(vec![]),
+        };
+        let violations = run_source_checks(checks, dir)?;
+        if !violations.is_empty() {
+            options.log("Source checks found violations:");
+            violations.iter().for_each(|violation| options.log(&format!("  {}", violation)));
+        }
+        options.emit("source_checks_finished", json!({ "violations": &violations }));
+        Ok(violations)
+    }
+
+    /// Whether violations of the source checks fail all testcases.
+    fn source_checks_failed(&self) -> bool {
+        self.violations.iter().any(|violation| violation.fail)
+    }
+
+    /// Exit code of the testrunner, reflecting the results of the last run.
+    pub fn exit_code(&self) -> i32 {
+        if self.binary.info.timed_out {
+            EXIT_BUILD_TIMEOUT
+        }
+        else if !self.binary.info.compiled {
+            EXIT_COMPILATION_FAILED
+        }
+        else if self.testresults.iter().all(|tc| tc.passed()) {
+            EXIT_PASSED
+        }
+        else {
+            EXIT_FAILED
+        }
+    }
+
+    pub fn options(&self) -> &TestrunnerOptions {
+        &self.options
+    }
+
+    /// Directory of the project, which the build commands are run in.
+    pub fn project_dir(&self) -> String {
+        match Build::from_project_definition(&self.project_definition) {
+            Some(build) => build.directory(&self.project_definition),
+            None => ".".to_owned(),
+        }
+    }
+
+    /// Compiles the project again and discards the results, to rerun the tests after changes.
+    pub fn recompile(&mut self) -> Result<(), TestrunnerError> {
+        self.testresults.clear();
+        self.binary = Arc::new(Self::compile(&self.project_definition, &self.options)?);
+        self.violations = Self::check_sources(&self.project_definition, &self.options, &self.project_dir())?;
+
+        let project_definition = Arc::downgrade(&self.project_definition);
+        let options = Arc::downgrade(&self.options);
+        let binary = Arc::downgrade(&self.binary);
+        self.testcases.iter_mut().try_for_each(|tc| {
+            let number = tc.get_test_meta().number;
+            tc.init(number, project_definition.clone(), options.clone(), binary.clone())
+        })
+    }
+
     pub fn deserialize_definition<'de, D>(deserializer: D) -> Result<Arc<ProjectDefinition>, D::Error>
         where D: Deserializer<'de>
     {
@@ -93,12 +327,65 @@ impl Testrunner {
     }
 
     pub fn run_tests(&mut self) -> Result<(), TestrunnerError> {
+        let tap = self.options.format == OutputFormat::Tap;
+        if tap {
+            println!("TAP version 14");
+        }
+
         if !self.binary.info.compiled {
-            println!("Compilation failed, skipping tests!");
+            let failure = if self.binary.info.timed_out { "Compilation timed out" } else { "Compilation failed" };
+            if tap {
+                println!("Bail out! {}", failure);
+            }
+            else {
+                println!("{}, skipping tests!", failure);
+            }
+            self.options.emit("run_finished", json!({ "passed": 0, "total": 0, "score": 0, "max_score": 0, "error": failure.to_lowercase() }));
             return Ok(());
         }
 
-        println!("\nStarting tests ...");
+        if self.source_checks_failed() {
+            self.options.log("\nSource checks failed, failing all tests!");
+        }
+        else {
+            self.options.log("\nStarting tests ...");
+        }
+        if tap {
+            println!("1..{}", self.testcases.len());
+        }
+        let result = self.run_testcases(tap);
+
+        let passed = self.testresults.iter().filter(|test| test.passed()).count();
+        let (score, max_score) = self.total_score();
+        self.options.emit("run_finished", json!({
+            "passed": passed,
+            "skipped": self.testresults.iter().filter(|tc| tc.skipped()).count(),
+            "total": self.testresults.len(),
+            "score": json_score(score),
+            "max_score": json_score(max_score),
+            "warning_penalty": json_score(self.binary.info.warning_penalty),
+            "error": result.as_ref().err().map(|err| err.to_string()),
+        }));
+        result?;
+
+        let prefix = if tap { "# " } else { "" };
+        println!("{}{}Passed testcases: {} / {}", if tap { "" } else { "\n" }, prefix, passed, self.testresults.len());
+        let skipped = self.testresults.iter().filter(|tc| tc.skipped()).count();
+        if let Some(reason) = self.testresults.iter().filter(|tc| tc.skipped()).find_map(|tc| tc.not_run_reason()) {
+            println!("{}Skipped testcases: {} ({})", prefix, skipped, reason);
+        }
+        if self.binary.info.warning_penalty > 0.0 {
+            println!("{}Penalty for compiler warnings: {}", prefix, self.binary.info.warning_penalty);
+        }
+        println!("{}Score: {} / {}", prefix, score, max_score);
+        Ok(())
+    }
+
+    fn run_testcases(&mut self, tap: bool) -> Result<(), TestrunnerError> {
+        let options = Arc::clone(&self.options);
+        let project_definition = Arc::clone(&self.project_definition);
+        let failures = AtomicUsize::new(0);
+        let source_checks_failed = self.source_checks_failed();
         scope(|scope| {
             self.testresults = match self.testcases.iter()
                 .parallel_map_scoped_custom(scope, |o| {
@@ -108,63 +395,296 @@ impl Testrunner {
                     else {
                         o
                     }
-                }, |tc| tc.run())
-                .try_fold(Vec::with_capacity(self.testcases.len()), |mut acc, tc| {
-                    let tc = tc?;
-
-                    if tc.protected() {
-                        println!("\nFinished testcase {}: ********", tc.number());
+                }, |tc| {
+                    if source_checks_failed {
+                        return Ok::<_, TestingError>(tc.not_run(NotRunReason::SourceChecks));
                     }
-                    else {
-                        println!("\nFinished testcase {}: {}", tc.number(), tc.name());
+                    // testcases already running when the limit is reached are finished regularly
+                    if options.max_failures.map_or(false, |max| failures.load(Ordering::SeqCst) >= max) {
+                        return Ok(tc.not_run(NotRunReason::MaxFailures));
                     }
-
-                    if tc.timeout() {
-                        println!("  Testcase ran into a timeout! Possibly failed capturing some/all output!");
-                    }
-
-                    if tc.truncated_output() {
-                        println!("  Truncating your output, because it's much longer than the reference output!");
-                    }
-
-                    println!("  Output-Diff: {}%", (tc.diff_distance() * 1000.0).floor() / 10.0);
-                    if let Some(distance) = tc.add_diff_distance() {
-                        println!("  File-Diff: {}%", (distance * 1000.0).floor() / 10.0);
+                    let meta = tc.get_test_meta();
+                    options.emit("testcase_started", json!({ "number": meta.number, "name": meta.name }));
+                    let result = tc.run_with_retries(&project_definition)?;
+                    if !result.passed() {
+                        failures.fetch_add(1, Ordering::SeqCst);
                     }
-
-                    if tc.exit_code().unwrap_or(-99) == tc.expected_exit_code().unwrap_or(0) {
-                        println!("  Exit-Code: correct");
+                    Ok(result)
+                })
+                .try_fold(Vec::with_capacity(self.testcases.len()), |mut acc, tc| {
+                    let tc = tc?;
+                    let mut payload = tc.get_json_entry()?;
+                    payload["number"] = json!(tc.number());
+                    self.options.emit("testcase_finished", payload);
+                    if tap {
+                        self.print_tap_testresult(tc.as_ref());
                     }
                     else {
-                        println!("  Exit-Code: incorrect");
-                    }
-
-                    if self.project_definition.use_valgrind.unwrap_or(true) {
-                        if tc.timeout() {
-                            println!("  Memory usage errors: n/a\n  Memory leaks: n/a");
-                        }
-                        else if !tc.mem_errors().is_some() || !tc.mem_leaks().is_some() {
-                            println!("  Memory usage errors: ?\n  Memory leaks: ?");
-                        }
-                        else {
-                            println!("  Memory usage errors: {}\n  Memory leaks: {}", tc.mem_errors().unwrap(), tc.mem_leaks().unwrap());
-                        }
+                        self.print_testresult(tc.as_ref());
                     }
-
-                    acc.push(tc);
+                    acc.push(Arc::from(tc));
                     Ok(acc)
                 }) {
                     Ok(results) => results,
                     Err(err) => return Err::<(), TestrunnerError>(err),
             };
             Ok(())
-        }).unwrap()?;
-        println!("\nPassed testcases: {} / {}", self.testresults.iter().filter(|test| test.passed()).count(), self.testresults.len());
-        Ok(())
+        }).unwrap()
+    }
+
+    fn print_testresult(&self, tc: &(dyn Testresult + Send + Sync)) {
+        if let Some(reason) = tc.not_run_reason() {
+            let status = if tc.skipped() { "Skipped" } else { "Failed" };
+            if tc.protected() {
+                println!("\n{} testcase {}: ********", status, tc.number());
+            }
+            else {
+                println!("\n{} testcase {}: {}", status, tc.number(), tc.name());
+            }
+            if !tc.skipped() {
+                println!("  Not run: {}", reason);
+            }
+            return;
+        }
+
+        if tc.protected() {
+            println!("\nFinished testcase {}: ********", tc.number());
+        }
+        else {
+            println!("\nFinished testcase {}: {}", tc.number(), tc.name());
+        }
+
+        if tc.timeout() {
+            println!("  Testcase ran into a timeout! Possibly failed capturing some/all output!");
+        }
+
+        if tc.truncated_output() {
+            println!("  Truncating your output, because it's much longer than the reference output!");
+        }
+
+        println!("  Output-Diff: {}%", (tc.diff_distance() * 1000.0).floor() / 10.0);
+        if let Some(distance) = tc.stderr_diff_distance() {
+            println!("  Stderr-Diff: {}%", (distance * 1000.0).floor() / 10.0);
+        }
+        if let Some(distance) = tc.add_diff_distance() {
+            println!("  File-Diff: {}%", (distance * 1000.0).floor() / 10.0);
+        }
+
+        if tc.exit_code_correct() {
+            println!("  Exit-Code: correct");
+        }
+        else {
+            println!("  Exit-Code: incorrect");
+        }
+
+        if let ExitOutcome::Signaled(signal, _) = tc.exit_outcome() {
+            println!("  Terminated by signal: {}", signal_name(signal));
+        }
+
+        if let Some(limit_exceeded) = tc.limit_exceeded() {
+            println!("  Resource limits: {}", limit_exceeded);
+        }
+
+        if self.project_definition.use_valgrind.unwrap_or(true) {
+            if tc.timeout() {
+                println!("  Memory usage errors: n/a\n  Memory leaks: n/a");
+            }
+            else if !tc.mem_errors().is_some() || !tc.mem_leaks().is_some() {
+                println!("  Memory usage errors: ?\n  Memory leaks: ?");
+            }
+            else {
+                println!("  Memory usage errors: {}\n  Memory leaks: {}", tc.mem_errors().unwrap(), tc.mem_leaks().unwrap());
+            }
+        }
+
+        println!("  Score: {} / {}", tc.score(), tc.max_score());
+
+        if tc.flaky() {
+            println!("  Flaky: passed after {} attempts", tc.attempts().len());
+        }
+        else if tc.attempts().len() > 1 {
+            println!("  Attempts: {}, all failed", tc.attempts().len());
+        }
+
+        if self.options.show_diff && !tc.passed() && !tc.protected() {
+            tc.unified_diffs(self.options.color).iter().for_each(|(title, diff)| {
+                println!("  {}:", title);
+                diff.lines().for_each(|line| println!("    {}", line));
+            });
+        }
+    }
+
+    // one TAP test point, followed by a YAML block with the details
+    fn print_tap_testresult(&self, tc: &(dyn Testresult + Send + Sync)) {
+        let status = if tc.passed() { "ok" } else { "not ok" };
+        let name = if self.options.protected_mode && tc.protected() { "redacted".to_owned() } else { tap_escape(&tc.name()) };
+        if let Some(reason) = tc.not_run_reason() {
+            if tc.skipped() {
+                println!("ok {} - {} # SKIP {}", tc.number(), name, reason);
+            }
+            else {
+                println!("not ok {} - {}", tc.number(), name);
+                println!("  ---\n  message: {}\n  ...", yaml_string(&reason.to_string()));
+            }
+            return;
+        }
+        println!("{} {} - {}", status, tc.number(), name);
+        if self.options.protected_mode && tc.protected() {
+            return;
+        }
+
+        let mut yaml = vec![format!("type: {}", tc.kind())];
+        yaml.push(format!("diff_distance: {}", json_score(tc.diff_distance())));
+        if let Some(distance) = tc.stderr_diff_distance() {
+            yaml.push(format!("stderr_diff_distance: {}", json_score(distance)));
+        }
+        if let Some(distance) = tc.add_diff_distance() {
+            yaml.push(format!("add_diff_distance: {}", json_score(distance)));
+        }
+        let exit_code = match tc.exit_code() {
+            Some(exit_code) => exit_code.to_string(),
+            None => yaml_string(&tc.exit_outcome().to_string()),
+        };
+        yaml.push(format!("exit_code: {}", exit_code));
+        let expected = match tc.expected_signal() {
+            Some(signal) => yaml_string(&signal_name(signal)),
+            None => tc.expected_exit_code().unwrap_or(0).to_string(),
+        };
+        yaml.push(format!("expected_exit_code: {}", expected));
+        yaml.push(format!("timeout: {}", tc.timeout()));
+        if let Some(limit_exceeded) = tc.limit_exceeded() {
+            yaml.push(format!("limit_exceeded: {}", yaml_string(&limit_exceeded.to_string())));
+        }
+        if let Some(mem_errors) = tc.mem_errors() {
+            yaml.push(format!("mem_errors: {}", mem_errors));
+        }
+        if let Some(mem_leaks) = tc.mem_leaks() {
+            yaml.push(format!("mem_leaks: {}", mem_leaks));
+        }
+        if tc.attempts().len() > 1 {
+            yaml.push(format!("attempts: {}", tc.attempts().len()));
+            yaml.push(format!("flaky: {}", tc.flaky()));
+        }
+        yaml.push(format!("score: {}", json_score(tc.score())));
+        yaml.push(format!("max_score: {}", json_score(tc.max_score())));
+        if !tc.passed() {
+            yaml.push("failures:".to_owned());
+            tc.failure_reasons().iter().for_each(|reason| yaml.push(format!("  - {}", yaml_string(reason))));
+        }
+
+        println!("  ---");
+        yaml.iter().for_each(|line| println!("  {}", line));
+        println!("  ...");
+    }
+
+    /// Total score after deducting the penalty for compiler warnings, and the maximal score.
+    pub fn total_score(&self) -> (f32, f32) {
+        let score = self.testresults.iter().map(|tc| tc.score()).sum::<f32>();
+        let score = (score - self.binary.info.warning_penalty).max(0.0);
+        let max_score = self.testresults.iter().map(|tc| tc.max_score()).sum::<f32>();
+        (round_score(score), round_score(max_score))
+    }
+
+    pub fn generate_html_report(&self, _protected_mode: bool) -> Result<String, TestrunnerError> {
+        // rendering consumes the template, so a copy without the testcases is rendered
+        let report = Testrunner {
+            project_definition: Arc::clone(&self.project_definition),
+            testcases: vec![],
+            testresults: self.testresults.clone(),
+            binary: Arc::clone(&self.binary),
+            violations: self.violations.clone(),
+            options: Arc::clone(&self.options),
+        };
+        Ok(report.render_once()?)
     }
 
-    pub fn generate_html_report(self, _protected_mode: bool) -> Result<String, TestrunnerError> {
-        Ok(self.render_once()?)
+    pub fn generate_markdown_report(&self) -> Result<String, TestrunnerError> {
+        let mut md = String::from("# Testreport\n\n");
+        if !self.binary.info.compiled {
+            md.push_str("**Could not compile project, no testcases were run!**\n\n");
+            md.push_str(&markdown_code_block("", self.binary.info.errors.as_deref().unwrap_or("")));
+            return Ok(md);
+        }
+
+        if let Some(warnings) = self.binary.info.warnings.as_ref() {
+            md.push_str("| Compiler Warning | Occurences |\n|---|---:|\n");
+            warnings.iter().for_each(|(warn, amount)| md.push_str(&format!("| {} | {} |\n", markdown_escape(warn), amount)));
+            md.push('\n');
+        }
+
+        let passed = self.testresults.iter().filter(|tc| tc.passed()).count();
+        let (score, max_score) = self.total_score();
+        md.push_str(&format!("**Passed testcases:** {} / {}  \n", passed, self.testresults.len()));
+        let skipped = self.testresults.iter().filter(|tc| tc.skipped()).count();
+        if skipped > 0 {
+            md.push_str(&format!("**Skipped testcases:** {}  \n", skipped));
+        }
+        if self.binary.info.warning_penalty > 0.0 {
+            md.push_str(&format!("**Penalty for compiler warnings:** {}  \n", self.binary.info.warning_penalty));
+        }
+        md.push_str(&format!("**Score:** {} / {}\n\n", score, max_score));
+
+        let use_valgrind = self.project_definition.use_valgrind.unwrap_or(true);
+        md.push_str("| Name | Passed | Score | Diff | Exit Code | Timeout | Mem Usage Errors | Mem Leaks |\n");
+        md.push_str("|---|:---:|:---:|:---:|:---:|:---:|:---:|:---:|\n");
+        for tc in self.testresults.iter() {
+            let name = if self.options.protected_mode && tc.protected() {
+                "*redacted*".to_owned()
+            }
+            else {
+                format!("#{:0>2}: {}", tc.number(), markdown_escape(&tc.name()))
+            };
+            let exit_code = if tc.exit_code_correct() {
+                "correct".to_owned()
+            }
+            else if let Some(signal) = tc.exit_outcome().signal() {
+                format!("incorrect ({})", signal_name(signal))
+            }
+            else {
+                "incorrect".to_owned()
+            };
+            let valgrind = |value: Option<i32>| {
+                if !use_valgrind || tc.timeout() {
+                    "n/a".to_owned()
+                }
+                else {
+                    value.map(|e| e.to_string()).unwrap_or("?".to_owned())
+                }
+            };
+            if tc.not_run_reason().is_some() {
+                md.push_str(&format!("| {} | {} | 0 / {} | | | | | |\n", name, if tc.skipped() { "skipped" } else { "&#x2718;" }, tc.max_score()));
+                continue;
+            }
+            md.push_str(&format!("| {} | {} | {} / {} | {}% | {} | {} | {} | {} |\n",
+                name,
+                if tc.flaky() { "&#x2714; (flaky)" } else if tc.passed() { "&#x2714;" } else { "&#x2718;" },
+                tc.score(), tc.max_score(),
+                (tc.similarity() * 1000.0).floor() / 10.0,
+                exit_code,
+                if tc.timeout() { "yes" } else { "no" },
+                valgrind(tc.mem_errors()),
+                valgrind(tc.mem_leaks())));
+        }
+
+        if !self.violations.is_empty() {
+            md.push_str("\n## Source Checks\n\n| File | Line | Violation | Pattern | Fails Tests |\n|---|---:|---|---|:---:|\n");
+            self.violations.iter().for_each(|violation| md.push_str(&format!("| {} | {} | {} | {} | {} |\n",
+                markdown_escape(&violation.file),
+                violation.line.map(|line| line.to_string()).unwrap_or_default(),
+                violation.kind,
+                // pipes have to be escaped in tables, even in code
+                markdown_code(&violation.pattern).replace('|', "\\|"),
+                if violation.fail { "yes" } else { "no" })));
+        }
+
+        md.push_str("\n## Testcases\n\n");
+        self.testresults.iter()
+            .filter(|tc| !(self.options.protected_mode && tc.protected()))
+            .for_each(|tc| {
+                md.push_str(&tc.get_markdown_entry_detailed());
+                md.push('\n');
+            });
+        Ok(md)
     }
 
     pub fn generate_json_report(&self) -> Result<String, TestrunnerError> {
@@ -175,9 +695,82 @@ impl Testrunner {
         }
         json.insert("testcases".to_owned(), serde_json::to_value(results)?);
         json.insert("binary".to_owned(), serde_json::to_value(&self.binary.info)?);
+        json.insert("source_checks".to_owned(), serde_json::to_value(&self.violations)?);
+        let (score, max_score) = self.total_score();
+        json.insert("score".to_owned(), serde_json::to_value(json_score(score))?);
+        json.insert("max_score".to_owned(), serde_json::to_value(json_score(max_score))?);
+        json.insert("warning_penalty".to_owned(), serde_json::to_value(json_score(self.binary.info.warning_penalty))?);
 
         Ok(serde_json::to_string_pretty(&json)?)
     }
+
+    pub fn generate_junit_report(&self) -> Result<String, TestrunnerError> {
+        let skipped = self.testresults.iter().filter(|tc| tc.skipped()).count();
+        let failures = self.testresults.iter().filter(|tc| !tc.passed()).count() - skipped;
+        let errors = if self.binary.info.compiled { 0 } else { 1 };
+        let tests = self.testresults.len() + errors;
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!("<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">\n", tests, failures, errors, skipped));
+        xml.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">\n",
+            xml_escape(&self.project_definition.binary_path), tests, failures, errors, skipped));
+
+        if !self.binary.info.compiled {
+            xml.push_str("    <testcase name=\"compilation\" classname=\"build\">\n");
+            xml.push_str(&format!("      <error message=\"compilation failed\">{}</error>\n",
+                xml_escape(self.binary.info.errors.as_deref().unwrap_or(""))));
+            xml.push_str("    </testcase>\n");
+        }
+
+        for tc in self.testresults.iter() {
+            let redacted = self.options.protected_mode && tc.protected();
+            let name = if redacted { "redacted".to_owned() } else { tc.name() };
+            xml.push_str(&format!("    <testcase name=\"#{:0>2}: {}\" classname=\"{}\">\n", tc.number(), xml_escape(&name), tc.kind()));
+            if let Some(reason) = tc.not_run_reason() {
+                let element = if tc.skipped() { "skipped" } else { "failure" };
+                xml.push_str(&format!("      <{} message=\"{}\"/>\n", element, reason));
+            }
+            else if !tc.passed() {
+                if redacted {
+                    xml.push_str("      <failure message=\"testcase failed\"/>\n");
+                }
+                else {
+                    let reasons = tc.failure_reasons();
+                    xml.push_str(&format!("      <failure message=\"{}\">{}</failure>\n",
+                        xml_escape(&reasons.join("; ")), xml_escape(&reasons.join("\n"))));
+                }
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        Ok(xml)
+    }
+}
+
+// `#` starts a directive in TAP test point descriptions
+fn tap_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('#', "\\#").replace('\n', " ")
+}
+
+// JSON strings are valid YAML flow scalars
+fn yaml_string(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.chars().filter_map(|c| {
+        match c {
+            '&' => Some("&amp;".to_owned()),
+            '<' => Some("&lt;".to_owned()),
+            '>' => Some("&gt;".to_owned()),
+            '"' => Some("&quot;".to_owned()),
+            '\'' => Some("&apos;".to_owned()),
+            // not allowed in XML 1.0, even when escaped
+            c if (c as u32) < 0x20 && c != '\n' && c != '\r' && c != '\t' => None,
+            c => Some(c.to_string()),
+        }
+    }).collect()
 }
 
 impl<'de> Deserialize<'de> for Box<dyn Test + Send + Sync> {
@@ -201,3 +794,27 @@ pub fn get_deserializer_registry() -> &'static DeserializerRegistry {
     &DESERIALIZER_REGISTRY
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_numbers_accepts_numbers_and_ranges() {
+        assert_eq!(Selection::parse_numbers("3"), Ok(vec![3..=3]));
+        assert_eq!(Selection::parse_numbers("3, 5-8,10 - 10"), Ok(vec![3..=3, 5..=8, 10..=10]));
+    }
+
+    #[test]
+    fn parse_numbers_rejects_reversed_ranges() {
+        assert_eq!(Selection::parse_numbers("8-5"), Err("invalid range: 8-5".to_owned()));
+    }
+
+    #[test]
+    fn parse_numbers_rejects_invalid_numbers() {
+        assert!(Selection::parse_numbers("a").is_err());
+        assert!(Selection::parse_numbers("3,").is_err());
+        assert!(Selection::parse_numbers("-3").is_err());
+        assert!(Selection::parse_numbers("3-5-8").is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use std::fs::{Metadata, read_dir, symlink_metadata};
+use std::path::Path;
+
+
+/// Calls `visit` for every file below `dir`, skipping hidden files and directories. Symlinks are not followed.
+pub fn walk_dir<F: FnMut(&Path, &Metadata)>(dir: &Path, visit: &mut F) {
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        match symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => walk_dir(&path, visit),
+            Ok(metadata) => visit(&path, &metadata),
+            Err(_) => {},
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use crate::util::walk_dir;
+
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Snapshot = HashMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Detects changes of the files in a directory by polling their modification times and sizes.
+pub struct Watcher {
+    dir: PathBuf,
+    snapshot: Snapshot,
+}
+
+impl Watcher {
+    pub fn new(dir: &str) -> Self {
+        let dir = PathBuf::from(dir);
+        let snapshot = Self::scan(&dir);
+        Watcher { dir, snapshot }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Accepts the current state of the directory, e.g. after files were generated by compiling and testing.
+    pub fn update(&mut self) {
+        self.snapshot = Self::scan(&self.dir);
+    }
+
+    /// Blocks until a file was added, removed or modified, and no further changes happened for one poll interval.
+    pub fn wait_for_change(&mut self) {
+        loop {
+            sleep(POLL_INTERVAL);
+            let mut snapshot = Self::scan(&self.dir);
+            if snapshot == self.snapshot {
+                continue;
+            }
+
+            // editors and `make` often write several files in a row
+            loop {
+                sleep(POLL_INTERVAL);
+                let next = Self::scan(&self.dir);
+                if next == snapshot {
+                    break;
+                }
+                snapshot = next;
+            }
+            self.snapshot = snapshot;
+            return;
+        }
+    }
+
+    fn scan(dir: &Path) -> Snapshot {
+        let mut snapshot = HashMap::new();
+        walk_dir(dir, &mut |path, metadata| {
+            snapshot.insert(path.to_path_buf(), (metadata.modified().ok(), metadata.len()));
+        });
+        snapshot
+    }
+}